@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, BufRead};
+use crate::messages::message::MessageKind;
+use crate::node_id::NodeId;
+use crate::trace::{TraceLine, TraceOperation, TraceRecord};
+
+pub struct Walk {
+    pub nodes: Vec<NodeId>,
+}
+
+impl Walk {
+    pub fn hops(&self) -> usize {
+        self.nodes.len().saturating_sub(1)
+    }
+
+    pub fn revisits(&self) -> HashMap<NodeId, u32> {
+        let mut visits: HashMap<NodeId, u32> = HashMap::new();
+
+        for node_id in &self.nodes {
+            *visits.entry(*node_id).or_default() += 1;
+        }
+
+        visits
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(node_id, count)| (node_id, count - 1))
+            .collect()
+    }
+
+    pub fn has_loop(&self) -> bool {
+        !self.revisits().is_empty()
+    }
+
+    fn new(node_id: NodeId) -> Self {
+        Self {
+            nodes: vec![node_id]
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TraceAnalysis {
+    neighbours: HashMap<NodeId, Vec<NodeId>>,
+    agents: BTreeMap<u32, Walk>,
+    requests: BTreeMap<(u32, u32), Walk>,
+    responses: BTreeMap<u32, Vec<Walk>>,
+    transmissions: HashMap<NodeId, u64>,
+    lost_hops: u64,
+    dropped_hops: u64,
+}
+
+impl TraceAnalysis {
+    // takes back the hop of a message that was lost in transit or dropped from a full mailbox,
+    // which the trace records right after its send or, when a full mailbox evicts its oldest
+    // message, later on. Either way it is still the last hop of its walk.
+    fn undeliver(&mut self, record: &TraceRecord) {
+        match record.operation {
+            TraceOperation::Lost => self.lost_hops += 1,
+            _ => self.dropped_hops += 1,
+        }
+
+        if record.sender == record.receiver {
+            if record.kind == MessageKind::Response {
+                if let Some(walks) = record.request_id.and_then(|request_id| self.responses.get_mut(&request_id)) {
+                    if let Some(index) = walks.iter().rposition(|walk| walk.nodes == [record.sender]) {
+                        walks.remove(index);
+                    }
+                }
+            }
+
+            return;
+        }
+
+        let hop = [record.sender, record.receiver];
+        let is_hop = |walk: &&mut Walk| walk.nodes.ends_with(&hop);
+
+        let walk = match record.kind {
+            MessageKind::Agent => record.agent_id.and_then(|agent_id| self.agents.get_mut(&agent_id)).filter(is_hop),
+            MessageKind::Request => record.request_id.zip(record.sequence).and_then(|key| self.requests.get_mut(&key)).filter(is_hop),
+            MessageKind::Response => record.request_id.and_then(|request_id| {
+                self.responses
+                    .get_mut(&request_id)?
+                    .iter_mut()
+                    .rev()
+                    .find(is_hop)
+            })
+        };
+
+        if let Some(walk) = walk {
+            walk.nodes.pop();
+
+            if let Some(count) = self.transmissions.get_mut(&record.receiver) {
+                *count -= 1;
+            }
+        }
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut analysis = Self::default();
+
+        for (index, line) in reader.lines().enumerate() {
+            let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", index + 1, error));
+            let line = line.map_err(|error| invalid(error.to_string()))?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // an untagged enum only says that no variant matched, so malformed JSON is reported on its own.
+            let line: TraceLine = serde_json::from_str(&line).map_err(|_| {
+                match serde_json::from_str::<serde_json::Value>(&line) {
+                    Err(error) => invalid(error.to_string()),
+                    Ok(_) => invalid("not a node or a trace record".to_string()),
+                }
+            })?;
+
+            let record = match line {
+                TraceLine::Node(node) => {
+                    analysis.neighbours.insert(node.node, node.neighbours);
+                    continue;
+                },
+                TraceLine::Record(record) => record
+            };
+
+            match record.operation {
+                TraceOperation::Send => {},
+                TraceOperation::Receive => continue,
+                TraceOperation::Lost | TraceOperation::Drop => {
+                    analysis.undeliver(&record);
+                    continue;
+                }
+            }
+
+            if record.sender == record.receiver {
+                if record.kind == MessageKind::Response {
+                    if let Some(request_id) = record.request_id {
+                        analysis.responses.entry(request_id).or_default().push(Walk::new(record.sender));
+                    }
+                }
+
+                continue;
+            }
+
+            *analysis.transmissions.entry(record.sender).or_default() += 1;
+            *analysis.transmissions.entry(record.receiver).or_default() += 1;
+
+            let walk = match record.kind {
+                MessageKind::Agent => record.agent_id.map(|agent_id| {
+                    analysis.agents
+                        .entry(agent_id)
+                        .or_insert_with(|| Walk::new(record.sender))
+                }),
+                MessageKind::Request => record.request_id.zip(record.sequence).map(|key| {
+                    analysis.requests
+                        .entry(key)
+                        .or_insert_with(|| Walk::new(record.sender))
+                }),
+                MessageKind::Response => record.request_id.and_then(|request_id| {
+                    analysis.responses
+                        .get_mut(&request_id)?
+                        .iter_mut()
+                        .find(|walk| walk.nodes.last() == Some(&record.sender))
+                })
+            };
+
+            if let Some(walk) = walk {
+                walk.nodes.push(record.receiver);
+            }
+        }
+
+        Ok(analysis)
+    }
+
+    pub fn agents(&self) -> impl Iterator<Item = (&u32, &Walk)> {
+        self.agents.iter()
+    }
+
+    pub fn requests(&self) -> impl Iterator<Item = (&(u32, u32), &Walk)> {
+        self.requests.iter()
+    }
+
+    pub fn responses(&self) -> impl Iterator<Item = (&u32, &Vec<Walk>)> {
+        self.responses.iter()
+    }
+
+    pub fn shortest_hops(&self, from: NodeId, to: NodeId) -> Option<usize> {
+        if from == to {
+            return Some(0);
+        }
+
+        let mut distances: HashMap<NodeId, usize> = HashMap::from([(from, 0)]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(node_id) = queue.pop_front() {
+            let distance = distances[&node_id];
+
+            for neighbour_id in self.neighbours.get(&node_id).into_iter().flatten() {
+                if distances.contains_key(neighbour_id) {
+                    continue;
+                }
+
+                if *neighbour_id == to {
+                    return Some(distance + 1);
+                }
+
+                distances.insert(*neighbour_id, distance + 1);
+                queue.push_back(*neighbour_id);
+            }
+        }
+
+        None
+    }
+
+    fn stretch(&self, walk: &Walk) -> Option<f64> {
+        let shortest_hops = self.shortest_hops(*walk.nodes.first()?, *walk.nodes.last()?)?;
+
+        if shortest_hops > 0 {
+            Some(walk.hops() as f64 / shortest_hops as f64)
+        } else {
+            None
+        }
+    }
+
+    pub fn report(&self, top: usize) -> AnalysisReport {
+        let answered_requests: Vec<&Walk> = self.requests
+            .iter()
+            .filter(|((request_id, _), walk)| {
+                self.responses
+                    .get(request_id)
+                    .into_iter()
+                    .flatten()
+                    .any(|response| response.nodes.first() == walk.nodes.last())
+            })
+            .map(|(_, walk)| walk)
+            .collect();
+
+        let response_walks: Vec<&Walk> = self.responses
+            .values()
+            .flatten()
+            .filter(|walk| walk.hops() > 0)
+            .collect();
+
+        let mut revisits: HashMap<NodeId, u32> = HashMap::new();
+
+        let walks = self.agents.values()
+            .chain(self.requests.values())
+            .chain(self.responses.values().flatten());
+
+        for walk in walks {
+            for (node_id, count) in walk.revisits() {
+                *revisits.entry(node_id).or_default() += count;
+            }
+        }
+
+        AnalysisReport {
+            agents: WalkSummary::new(self.agents.values(), |_| None),
+            requests: WalkSummary::new(self.requests.values(), |_| None),
+            answered_requests: WalkSummary::new(answered_requests.into_iter(), |walk| self.stretch(walk)),
+            responses: WalkSummary::new(response_walks.into_iter(), |walk| self.stretch(walk)),
+            revisited_nodes: top_nodes(revisits.into_iter().map(|(node_id, count)| (node_id, count as u64)), top),
+            hot_spot_nodes: top_nodes(self.transmissions.iter().map(|(node_id, count)| (*node_id, *count)), top),
+            lost_hops: self.lost_hops,
+            dropped_hops: self.dropped_hops,
+        }
+    }
+}
+
+fn top_nodes(counts: impl Iterator<Item = (NodeId, u64)>, top: usize) -> Vec<(NodeId, u64)> {
+    let mut counts: Vec<(NodeId, u64)> = counts.collect();
+
+    counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(top);
+
+    counts
+}
+
+#[derive(Default)]
+pub struct WalkSummary {
+    pub walks: usize,
+    pub hops_mean: f64,
+    pub walks_with_loops: usize,
+    pub revisits: u64,
+    pub stretch_mean: Option<f64>,
+    pub stretch_max: Option<f64>,
+}
+
+impl WalkSummary {
+    fn new<'a>(walks: impl Iterator<Item = &'a Walk>, stretch: impl Fn(&Walk) -> Option<f64>) -> Self {
+        let mut summary = Self::default();
+        let mut hops = 0;
+        let mut stretches = Vec::new();
+
+        for walk in walks {
+            let revisits: u32 = walk.revisits().values().sum();
+
+            summary.walks += 1;
+            summary.revisits += revisits as u64;
+            summary.walks_with_loops += (revisits > 0) as usize;
+            hops += walk.hops();
+
+            if let Some(stretch) = stretch(walk) {
+                stretches.push(stretch);
+            }
+        }
+
+        if summary.walks > 0 {
+            summary.hops_mean = hops as f64 / summary.walks as f64;
+        }
+
+        if !stretches.is_empty() {
+            summary.stretch_mean = Some(stretches.iter().sum::<f64>() / stretches.len() as f64);
+            summary.stretch_max = stretches.into_iter().reduce(f64::max);
+        }
+
+        summary
+    }
+}
+
+impl fmt::Display for WalkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "walks: {}, hops mean: {:.1}, with loops: {}, revisits: {}",
+            self.walks,
+            self.hops_mean,
+            self.walks_with_loops,
+            self.revisits
+        )?;
+
+        if let (Some(mean), Some(max)) = (self.stretch_mean, self.stretch_max) {
+            write!(f, ", stretch mean: {:.2}, max: {:.2}", mean, max)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct AnalysisReport {
+    pub agents: WalkSummary,
+    pub requests: WalkSummary,
+    pub answered_requests: WalkSummary,
+    pub responses: WalkSummary,
+    pub revisited_nodes: Vec<(NodeId, u64)>,
+    pub hot_spot_nodes: Vec<(NodeId, u64)>,
+    pub lost_hops: u64,
+    pub dropped_hops: u64,
+}
+
+impl fmt::Display for AnalysisReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "agents: {}", self.agents)?;
+        writeln!(f, "requests: {}", self.requests)?;
+        writeln!(f, "answered requests: {}", self.answered_requests)?;
+        writeln!(f, "responses: {}", self.responses)?;
+        writeln!(f, "undelivered hops: {} lost, {} dropped", self.lost_hops, self.dropped_hops)?;
+
+        writeln!(f, "most revisited nodes:")?;
+
+        for (node_id, count) in &self.revisited_nodes {
+            writeln!(f, "  {:>10} {:>8}", node_id, count)?;
+        }
+
+        write!(f, "hot-spot nodes (transmissions sent and received):")?;
+
+        for (node_id, count) in &self.hot_spot_nodes {
+            write!(f, "\n  {:>10} {:>8}", node_id, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOPOLOGY: &str = r#"
+{"node":0,"x":0.0,"y":0.0,"neighbours":[1]}
+{"node":1,"x":10.0,"y":0.0,"neighbours":[0,2]}
+{"node":2,"x":20.0,"y":0.0,"neighbours":[1]}
+"#;
+
+    fn analyze(records: &str) -> TraceAnalysis {
+        TraceAnalysis::from_reader(format!("{}{}", TOPOLOGY, records).as_bytes()).unwrap()
+    }
+
+    fn hot_spots(analysis: &TraceAnalysis) -> Vec<(u32, u64)> {
+        analysis
+            .report(10)
+            .hot_spot_nodes
+            .into_iter()
+            .map(|(node_id, count)| (node_id.0, count))
+            .collect()
+    }
+
+    #[test]
+    fn reconstructs_walks_and_hot_spots() {
+        let analysis = analyze(r#"
+{"tick":1,"operation":"send","sender":0,"receiver":0,"kind":"agent","hops":0,"agent_id":7,"event_ids":[]}
+{"tick":2,"operation":"send","sender":0,"receiver":1,"kind":"agent","hops":1,"agent_id":7,"event_ids":[]}
+{"tick":2,"operation":"receive","sender":0,"receiver":1,"kind":"agent","hops":1,"agent_id":7,"event_ids":[]}
+{"tick":3,"operation":"send","sender":1,"receiver":2,"kind":"agent","hops":2,"agent_id":7,"event_ids":[]}
+{"tick":4,"operation":"send","sender":2,"receiver":1,"kind":"agent","hops":3,"agent_id":7,"event_ids":[]}
+"#);
+
+        let (_, walk) = analysis.agents().next().unwrap();
+
+        assert_eq!(walk.hops(), 3);
+        assert!(walk.has_loop());
+        assert_eq!(walk.revisits(), HashMap::from([(NodeId(1), 1)]));
+        assert_eq!(hot_spots(&analysis), vec![(1, 3), (2, 2), (0, 1)]);
+    }
+
+    #[test]
+    fn lost_and_dropped_hops_are_not_delivered() {
+        let analysis = analyze(r#"
+{"tick":1,"operation":"send","sender":0,"receiver":1,"kind":"agent","hops":1,"agent_id":7,"event_ids":[]}
+{"tick":2,"operation":"send","sender":1,"receiver":2,"kind":"agent","hops":2,"agent_id":7,"event_ids":[]}
+{"tick":2,"operation":"lost","sender":1,"receiver":2,"kind":"agent","hops":2,"agent_id":7,"event_ids":[]}
+{"tick":3,"operation":"send","sender":0,"receiver":1,"kind":"request","hops":1,"request_id":5,"sequence":1,"event_id":9}
+{"tick":4,"operation":"send","sender":1,"receiver":2,"kind":"request","hops":2,"request_id":5,"sequence":1,"event_id":9}
+{"tick":5,"operation":"send","sender":2,"receiver":2,"kind":"response","hops":0,"request_id":5,"event_id":9}
+{"tick":6,"operation":"send","sender":2,"receiver":1,"kind":"response","hops":1,"request_id":5,"event_id":9}
+{"tick":8,"operation":"drop","sender":2,"receiver":1,"kind":"response","hops":1,"request_id":5,"event_id":9}
+"#);
+
+        let (_, agent) = analysis.agents().next().unwrap();
+        let (_, request) = analysis.requests().next().unwrap();
+        let (_, responses) = analysis.responses().next().unwrap();
+        let report = analysis.report(10);
+
+        assert_eq!(agent.nodes, vec![NodeId(0), NodeId(1)]);
+        assert_eq!(request.hops(), 2);
+        assert_eq!(responses[0].nodes, vec![NodeId(2)]);
+        assert_eq!(report.responses.walks, 0);
+        assert_eq!((report.lost_hops, report.dropped_hops), (1, 1));
+        assert_eq!(hot_spots(&analysis), vec![(1, 4), (0, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn bad_lines_are_reported_with_their_number() {
+        let error = |trace: &[u8]| TraceAnalysis::from_reader(trace).err().unwrap().to_string();
+        let node = r#"{"node":0,"x":0.0,"y":0.0,"neighbours":[]}"#;
+
+        assert_eq!(
+            error(format!("{}\n\n{{\"tick\":1,\"operation\":\n", node).as_bytes()),
+            "line 3: EOF while parsing a value at line 1 column 22"
+        );
+        assert_eq!(error(format!("{}\n{{\"tick\":1}}\n", node).as_bytes()), "line 2: not a node or a trace record");
+        assert!(error(b"\n\xff\n").starts_with("line 2: "));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::f64::consts::SQRT_2;
+use std::fmt;
+use std::str::FromStr;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use crate::node_id::NodeId;
+use crate::position::Position;
+
+// loss probabilities of a link between two nodes `distance` apart, where `range` is the
+// transmission range of the sender. `Distance` rises from no loss next to the sender to
+// certain loss at the range, `Shadowing` is log-normal shadowing calibrated to lose half
+// the messages at the range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ChannelModel {
+    #[default]
+    Perfect,
+    Constant { loss: f64 },
+    Distance { exponent: f64 },
+    Shadowing { path_loss_exponent: f64, sigma: f64 },
+}
+
+// the standard normal distribution function, using the erf approximation 7.1.26 of
+// Abramowitz and Stegun (absolute error below 1.5e-7).
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let polynomial = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - polynomial * (-z * z).exp();
+
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+impl ChannelModel {
+    pub fn loss_probability(&self, distance: f64, range: f64) -> f64 {
+        let probability = match *self {
+            ChannelModel::Perfect => 0.0,
+            ChannelModel::Constant { loss } => loss,
+            ChannelModel::Distance { exponent } => {
+                if range > 0.0 {
+                    (distance / range).powf(exponent)
+                } else {
+                    1.0
+                }
+            },
+            ChannelModel::Shadowing { path_loss_exponent, sigma } => {
+                if distance <= 0.0 {
+                    0.0
+                } else if sigma <= 0.0 {
+                    if distance <= range { 0.0 } else { 1.0 }
+                } else {
+                    // the margin in dB by which the mean path loss stays below the one at the range.
+                    let margin = 10.0 * path_loss_exponent * (range / distance).log10();
+
+                    1.0 - normal_cdf(margin / sigma)
+                }
+            }
+        };
+
+        probability.clamp(0.0, 1.0)
+    }
+}
+
+impl FromStr for ChannelModel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.trim().split(':');
+        let name = parts.next().unwrap_or_default();
+
+        let parameters = parts
+            .map(|part| part.trim().parse::<f64>().map_err(|_| format!("invalid channel parameter '{}'", part)))
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        let parameter = |index: usize, default: Option<f64>| {
+            parameters
+                .get(index)
+                .copied()
+                .or(default)
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| format!("channel '{}' needs a non-negative parameter {}", name, index + 1))
+        };
+
+        let (model, count) = match name {
+            "perfect" => (ChannelModel::Perfect, 0),
+            "constant" => (ChannelModel::Constant { loss: parameter(0, None)? }, 1),
+            "distance" => (ChannelModel::Distance { exponent: parameter(0, Some(2.0))? }, 1),
+            "shadowing" => (ChannelModel::Shadowing { path_loss_exponent: parameter(0, Some(3.0))?, sigma: parameter(1, Some(4.0))? }, 2),
+            _ => return Err(format!("expected perfect, constant:<loss>, distance[:<exponent>] or shadowing[:<exponent>[:<sigma>]], got '{}'", value)),
+        };
+
+        if parameters.len() > count {
+            return Err(format!("too many parameters for channel '{}'", name));
+        }
+
+        if let ChannelModel::Constant { loss } = model {
+            if loss > 1.0 {
+                return Err(format!("loss must be between 0 and 1, got {}", loss));
+            }
+        }
+
+        Ok(model)
+    }
+}
+
+impl TryFrom<String> for ChannelModel {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ChannelModel> for String {
+    fn from(model: ChannelModel) -> Self {
+        model.to_string()
+    }
+}
+
+impl fmt::Display for ChannelModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChannelModel::Perfect => write!(f, "perfect"),
+            ChannelModel::Constant { loss } => write!(f, "constant:{}", loss),
+            ChannelModel::Distance { exponent } => write!(f, "distance:{}", exponent),
+            ChannelModel::Shadowing { path_loss_exponent, sigma } => write!(f, "shadowing:{}:{}", path_loss_exponent, sigma),
+        }
+    }
+}
+
+pub struct Channel {
+    model: ChannelModel,
+    ranges: Vec<f64>,
+    qualities: HashMap<(NodeId, NodeId), f64>,
+    positions: Vec<Position>,
+    rng: StdRng,
+}
+
+impl Channel {
+    // links listed with a quality below 1 lose messages on top of the model.
+    pub fn is_lost(&mut self, sender_id: NodeId, node_id: NodeId) -> bool {
+        let distance = self.positions[sender_id.index()].get_distance_to(&self.positions[node_id.index()]);
+        let quality = self.qualities.get(&(sender_id, node_id)).copied().unwrap_or(1.0);
+        let probability = 1.0 - quality * (1.0 - self.model.loss_probability(distance, self.ranges[sender_id.index()]));
+
+        probability > 0.0 && self.rng.gen::<f64>() < probability
+    }
+
+    pub fn new(model: ChannelModel, ranges: Vec<f64>, qualities: HashMap<(NodeId, NodeId), f64>, positions: Vec<Position>, rng: StdRng) -> Self {
+        Self {
+            model,
+            ranges,
+            qualities,
+            positions,
+            rng
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_models_round_trip() {
+        assert_eq!("perfect".parse::<ChannelModel>(), Ok(ChannelModel::Perfect));
+        assert_eq!("constant:0.25".parse::<ChannelModel>(), Ok(ChannelModel::Constant { loss: 0.25 }));
+        assert_eq!("distance".parse::<ChannelModel>().unwrap().to_string(), "distance:2");
+        assert_eq!("shadowing:2.5:6".parse::<ChannelModel>().unwrap().to_string(), "shadowing:2.5:6");
+        assert!("constant".parse::<ChannelModel>().is_err());
+        assert!("constant:1.5".parse::<ChannelModel>().is_err());
+        assert!("distance:2:3".parse::<ChannelModel>().is_err());
+    }
+
+    #[test]
+    fn shadowing_loses_half_at_the_range() {
+        let model = ChannelModel::Shadowing { path_loss_exponent: 3.0, sigma: 4.0 };
+
+        assert!((model.loss_probability(15.0, 15.0) - 0.5).abs() < 1e-6);
+        assert!(model.loss_probability(5.0, 15.0) < 0.01);
+        assert!(model.loss_probability(30.0, 15.0) > 0.95);
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::Deserialize;
+use crate::export::OutputFormat;
+use crate::obstacle::Obstacle;
+use crate::sensornetwork::SensorNetworkOptions;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioConfig {
+    pub layout_file_path: Option<String>,
+    pub seed: Option<u64>,
+    pub iterations: Option<u32>,
+    #[serde(default)]
+    pub options: SensorNetworkOptions,
+    pub output: Option<OutputFormat>,
+    pub output_file: Option<String>,
+    pub trace_file: Option<String>,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "could not read config: {}", error),
+            ConfigError::Toml(error) => write!(f, "invalid config: {}", error),
+            ConfigError::Json(error) => write!(f, "invalid config: {}", error),
+            ConfigError::Invalid(error) => write!(f, "invalid config: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ScenarioConfig {
+    fn validate(self) -> Result<Self, ConfigError> {
+        for (index, obstacle) in self.obstacles.iter().enumerate() {
+            obstacle.validate().map_err(|error| ConfigError::Invalid(format!("obstacle {}: {}", index + 1, error)))?;
+        }
+
+        Ok(self)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, ConfigError> {
+        toml::from_str::<Self>(contents).map_err(ConfigError::Toml)?.validate()
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str::<Self>(contents).map_err(ConfigError::Json)?.validate()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&contents),
+            _ => Self::from_toml(&contents),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_and_json_read_the_same_scenario() {
+        let toml = ScenarioConfig::from_toml(
+            "layout_file_path = \"layout.txt\"\nseed = 42\noutput = \"json\"\n\n[options]\nagent_max_hops = 60\nneighbour_range = 12.5\n"
+        ).unwrap();
+        let json = ScenarioConfig::from_json(
+            r#"{"layout_file_path": "layout.txt", "seed": 42, "output": "json", "options": {"agent_max_hops": 60, "neighbour_range": 12.5}}"#
+        ).unwrap();
+
+        for config in [toml, json] {
+            assert_eq!(config.layout_file_path.as_deref(), Some("layout.txt"));
+            assert_eq!(config.seed, Some(42));
+            assert_eq!(config.iterations, None);
+            assert_eq!(config.output, Some(OutputFormat::Json));
+            assert_eq!(config.options.agent_max_hops, 60);
+            assert_eq!(config.options.neighbour_range, 12.5);
+            assert_eq!(config.options.request_ticks, SensorNetworkOptions::default().request_ticks);
+            assert!(config.obstacles.is_empty());
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let errors = [
+            ScenarioConfig::from_toml("sead = 42\n").unwrap_err(),
+            ScenarioConfig::from_toml("[options]\nagent_max_hop = 60\n").unwrap_err(),
+            ScenarioConfig::from_json(r#"{"options": {"neighbor_range": 12.5}}"#).unwrap_err(),
+            ScenarioConfig::from_toml("[[obstacles]]\npoints = [[0.0, 0.0], [1.0, 1.0]]\nattenuation = 0.5\n").unwrap_err(),
+        ];
+
+        for (error, key) in errors.iter().zip(["sead", "agent_max_hop", "neighbor_range", "attenuation"]) {
+            assert!(error.to_string().contains(&format!("unknown field `{}`", key)), "{}", error);
+        }
+    }
+
+    #[test]
+    fn obstacles_are_validated_with_their_position() {
+        let error = ScenarioConfig::from_toml(
+            "[[obstacles]]\npoints = [[0.0, 0.0], [1.0, 1.0]]\n\n[[obstacles]]\npoints = [[0.0, 0.0]]\n"
+        ).unwrap_err();
+
+        assert!(matches!(error, ConfigError::Invalid(_)));
+        assert_eq!(error.to_string(), "invalid config: obstacle 2: obstacle needs at least two points");
+    }
+}
@@ -0,0 +1,281 @@
+use std::io::{self, Write};
+use serde::{Deserialize, Serialize};
+use crate::metrics::message_counters::MessageCounts;
+use crate::metrics::queue_counters::QueueCounters;
+use crate::metrics::query_record::QueryRecord;
+use crate::metrics::query_report::QueryReport;
+use crate::node_id::NodeId;
+use crate::obstacle::{Obstacle, ObstacleReport};
+use crate::sensornetwork::SensorNetworkOptions;
+use crate::simulation::Simulation;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RunConfig {
+    pub config_file_path: Option<String>,
+    pub layout_file_path: Option<String>,
+    pub nodes: usize,
+    pub obstacles: Vec<Obstacle>,
+    pub seed: u64,
+    pub iterations: u32,
+    pub options: SensorNetworkOptions,
+    pub output: Option<OutputFormat>,
+    pub output_file: Option<String>,
+    pub trace_file: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct QueryRow {
+    pub id: u32,
+    pub node_id: NodeId,
+    pub event_id: u32,
+    pub created_tick: u32,
+    pub sequence: u32,
+    pub completed_tick: Option<u32>,
+    pub latency: Option<u32>,
+    pub request_hops: Option<u32>,
+    pub response_hops: Option<u32>,
+    pub outcome: &'static str,
+    pub failure_reason: Option<&'static str>,
+}
+
+impl From<&QueryRecord> for QueryRow {
+    fn from(record: &QueryRecord) -> Self {
+        Self {
+            id: record.id,
+            node_id: record.node_id,
+            event_id: record.event_id,
+            created_tick: record.created_tick,
+            sequence: record.sequence,
+            completed_tick: record.completed_tick,
+            latency: record.latency(),
+            request_hops: record.request_hops,
+            response_hops: record.response_hops,
+            outcome: record.outcome.as_str(),
+            failure_reason: record.outcome.failure_reason().map(|reason| reason.as_str()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RunSummary {
+    pub queries: QueryReport,
+    pub messages: MessageCounts,
+    pub lost: MessageCounts,
+    pub no_neighbour: MessageCounts,
+    pub queues: QueueCounters,
+    pub obstacles: ObstacleReport,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RunResults {
+    pub config: RunConfig,
+    pub summary: RunSummary,
+    pub queries: Vec<QueryRow>,
+}
+
+impl RunResults {
+    pub fn new(config: RunConfig, simulation: &Simulation) -> Self {
+        Self {
+            config,
+            summary: RunSummary {
+                queries: simulation.query_metrics().report(),
+                messages: *simulation.message_counters().total(),
+                lost: *simulation.message_counters().lost(),
+                no_neighbour: *simulation.message_counters().no_neighbour(),
+                queues: simulation.queue_counters().clone(),
+                obstacles: simulation.obstacle_report(),
+            },
+            queries: simulation
+                .query_metrics()
+                .records()
+                .map(QueryRow::from)
+                .collect(),
+        }
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut comments = Vec::new();
+
+        flatten(&serde_json::to_value(&self.config)?, "config", &mut comments);
+        flatten(&serde_json::to_value(&self.summary)?, "summary", &mut comments);
+
+        for (key, value) in comments {
+            writeln!(writer, "# {}={}", key, value)?;
+        }
+
+        writeln!(
+            writer,
+            "id,node_id,event_id,created_tick,sequence,completed_tick,latency,request_hops,response_hops,outcome,failure_reason"
+        )?;
+
+        for row in &self.queries {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                row.id,
+                row.node_id,
+                row.event_id,
+                row.created_tick,
+                row.sequence,
+                optional(row.completed_tick),
+                optional(row.latency),
+                optional(row.request_hops),
+                optional(row.response_hops),
+                row.outcome,
+                row.failure_reason.unwrap_or_default()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn optional(value: Option<u32>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn flatten(value: &serde_json::Value, prefix: &str, entries: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                flatten(value, &format!("{}.{}", prefix, key), entries);
+            }
+        },
+        serde_json::Value::Null => entries.push((prefix.to_string(), String::new())),
+        serde_json::Value::String(value) => entries.push((prefix.to_string(), value.clone())),
+        value => entries.push((prefix.to_string(), value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Layout;
+    use crate::position::Position;
+    use crate::simulation::SimulationBuilder;
+
+    fn results() -> RunResults {
+        let positions: Vec<Position> = (0..16).map(|index| Position::new((index % 4) as f64 * 10.0, (index / 4) as f64 * 10.0)).collect();
+        let options = SensorNetworkOptions {
+            event_probability: 200,
+            request_ticks: 20,
+            request_max_hops: 3,
+            request_retry_multiplier: 1,
+            ..SensorNetworkOptions::default()
+        };
+        let config = RunConfig {
+            config_file_path: None,
+            layout_file_path: Some("layout.txt".to_string()),
+            nodes: positions.len(),
+            obstacles: vec![Obstacle { points: vec![[0.0, 15.0], [30.0, 15.0]], loss: 0.5 }],
+            seed: 3,
+            iterations: 300,
+            options: options.clone(),
+            output: Some(OutputFormat::Csv),
+            output_file: None,
+            trace_file: None,
+        };
+
+        let mut layout = Layout::from(positions);
+
+        layout.obstacles = config.obstacles.clone();
+
+        let mut simulation = SimulationBuilder::new(layout, options)
+            .seed(config.seed)
+            .build();
+
+        simulation.run(config.iterations);
+
+        RunResults::new(config, &simulation)
+    }
+
+    #[test]
+    fn json_holds_the_config_summary_and_every_query() {
+        let results = results();
+        let mut buffer = Vec::new();
+
+        results.write_json(&mut buffer).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+
+        assert!(!results.queries.is_empty());
+        assert_eq!(value["config"]["seed"], 3);
+        assert_eq!(value["config"]["layout_file_path"], "layout.txt");
+        assert_eq!(value["config"]["output"], "csv");
+        assert!(value["config"]["trace_file"].is_null());
+        assert_eq!(value["config"]["obstacles"][0]["points"][1], serde_json::json!([30.0, 15.0]));
+        assert_eq!(value["config"]["obstacles"][0]["loss"], 0.5);
+        assert_eq!(value["summary"]["obstacles"]["attenuated"], results.summary.obstacles.attenuated);
+        assert_eq!(value["summary"]["messages"]["bytes"], results.summary.messages.bytes);
+        assert_eq!(value["queries"].as_array().unwrap().len(), results.queries.len());
+
+        let first = &value["queries"][0];
+        let row = &results.queries[0];
+
+        assert_eq!(first["id"], row.id);
+        assert_eq!(first["node_id"], row.node_id.0);
+        assert_eq!(first["outcome"], row.outcome);
+    }
+
+    #[test]
+    fn csv_has_comment_lines_then_one_row_per_query() {
+        let results = results();
+        let mut buffer = Vec::new();
+
+        results.write_csv(&mut buffer).unwrap();
+
+        let csv = String::from_utf8(buffer).unwrap();
+        let (comments, table): (Vec<&str>, Vec<&str>) = csv.lines().partition(|line| line.starts_with('#'));
+
+        assert!(comments.contains(&"# config.seed=3"));
+        assert!(comments.contains(&"# config.layout_file_path=layout.txt"));
+        assert!(comments.contains(&"# config.output=csv"));
+        assert!(comments.contains(&"# config.trace_file="));
+        assert!(comments.contains(&r#"# config.obstacles=[{"loss":0.5,"points":[[0.0,15.0],[30.0,15.0]]}]"#));
+        assert!(comments.contains(&format!("# summary.messages.bytes={}", results.summary.messages.bytes).as_str()));
+
+        assert_eq!(
+            table[0],
+            "id,node_id,event_id,created_tick,sequence,completed_tick,latency,request_hops,response_hops,outcome,failure_reason"
+        );
+        assert_eq!(table.len(), results.queries.len() + 1);
+        assert!(table[1..].iter().all(|row| row.split(',').count() == 11));
+    }
+
+    #[test]
+    fn csv_leaves_missing_values_empty() {
+        let row = QueryRow {
+            id: 4,
+            node_id: NodeId(2),
+            event_id: 1,
+            created_tick: 10,
+            sequence: 0,
+            completed_tick: None,
+            latency: None,
+            request_hops: Some(3),
+            response_hops: None,
+            outcome: "pending",
+            failure_reason: None,
+        };
+        let mut results = results();
+        let mut buffer = Vec::new();
+
+        results.queries = vec![row];
+        results.write_csv(&mut buffer).unwrap();
+
+        assert!(String::from_utf8(buffer).unwrap().ends_with("\n4,2,1,10,0,,,3,,pending,\n"));
+    }
+}
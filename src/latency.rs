@@ -0,0 +1,185 @@
+use std::fmt;
+use std::str::FromStr;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use crate::node_id::NodeId;
+use crate::position::Position;
+
+// the longest delay of a hop in ticks, and of its jitter, so that delivery ticks stay far
+// from overflowing.
+pub const MAX_DELAY: u32 = 1_000_000;
+
+// per-hop delay distributions in ticks. Sampled delays are rounded up to whole ticks and
+// are between one tick and `MAX_DELAY`, so `Fixed { ticks: 1 }` is the plain hop-by-hop timing.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum LatencyModel {
+    Fixed { ticks: u32 },
+    Uniform { min: u32, max: u32 },
+    Exponential { mean: f64 },
+    Distance { ticks_per_unit: f64 },
+}
+
+impl Default for LatencyModel {
+    fn default() -> Self {
+        LatencyModel::Fixed { ticks: 1 }
+    }
+}
+
+impl LatencyModel {
+    pub fn sample(&self, distance: f64, rng: &mut StdRng) -> u32 {
+        let ticks = match *self {
+            LatencyModel::Fixed { ticks } => ticks as f64,
+            LatencyModel::Uniform { min, max } => rng.gen_range(min..=max) as f64,
+            LatencyModel::Exponential { mean } => -mean * (1.0 - rng.gen::<f64>()).ln(),
+            LatencyModel::Distance { ticks_per_unit } => distance * ticks_per_unit,
+        };
+
+        (ticks.ceil().min(MAX_DELAY as f64) as u32).max(1)
+    }
+}
+
+impl FromStr for LatencyModel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.trim().split(':');
+        let name = parts.next().unwrap_or_default();
+
+        let parameters = parts
+            .map(|part| part.trim().parse::<f64>().map_err(|_| format!("invalid latency parameter '{}'", part)))
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        let parameter = |index: usize| {
+            parameters
+                .get(index)
+                .copied()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| format!("latency '{}' needs a non-negative parameter {}", name, index + 1))
+        };
+
+        let ticks = |index: usize| {
+            parameter(index).and_then(|value| {
+                if value >= 1.0 && value.fract() == 0.0 && value <= MAX_DELAY as f64 {
+                    Ok(value as u32)
+                } else {
+                    Err(format!("latency '{}' needs a whole number of ticks between 1 and {}, got {}", name, MAX_DELAY, value))
+                }
+            })
+        };
+
+        let (model, count) = match name {
+            "fixed" => (LatencyModel::Fixed { ticks: ticks(0)? }, 1),
+            "uniform" => (LatencyModel::Uniform { min: ticks(0)?, max: ticks(1)? }, 2),
+            "exponential" => (LatencyModel::Exponential { mean: parameter(0)? }, 1),
+            "distance" => (LatencyModel::Distance { ticks_per_unit: parameter(0)? }, 1),
+            _ => return Err(format!("expected fixed:<ticks>, uniform:<min>:<max>, exponential:<mean> or distance:<ticks per unit>, got '{}'", value)),
+        };
+
+        if parameters.len() > count {
+            return Err(format!("too many parameters for latency '{}'", name));
+        }
+
+        if let LatencyModel::Uniform { min, max } = model {
+            if min > max {
+                return Err(format!("uniform latency needs min <= max, got {} and {}", min, max));
+            }
+        }
+
+        if let LatencyModel::Exponential { mean } = model {
+            if mean > MAX_DELAY as f64 {
+                return Err(format!("exponential latency needs a mean of at most {} ticks, got {}", MAX_DELAY, mean));
+            }
+        }
+
+        Ok(model)
+    }
+}
+
+impl TryFrom<String> for LatencyModel {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<LatencyModel> for String {
+    fn from(model: LatencyModel) -> Self {
+        model.to_string()
+    }
+}
+
+impl fmt::Display for LatencyModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LatencyModel::Fixed { ticks } => write!(f, "fixed:{}", ticks),
+            LatencyModel::Uniform { min, max } => write!(f, "uniform:{}:{}", min, max),
+            LatencyModel::Exponential { mean } => write!(f, "exponential:{}", mean),
+            LatencyModel::Distance { ticks_per_unit } => write!(f, "distance:{}", ticks_per_unit),
+        }
+    }
+}
+
+pub struct Latency {
+    model: LatencyModel,
+    jitter: u32,
+    positions: Vec<Position>,
+    rng: StdRng,
+}
+
+impl Latency {
+    // ticks a message from `sender_id` takes to reach `node_id`, plus up to `jitter` extra ticks.
+    pub fn delay(&mut self, sender_id: NodeId, node_id: NodeId) -> u32 {
+        let distance = self.positions[sender_id.index()].get_distance_to(&self.positions[node_id.index()]);
+        let delay = self.model.sample(distance, &mut self.rng);
+
+        if self.jitter > 0 {
+            delay.saturating_add(self.rng.gen_range(0..=self.jitter))
+        } else {
+            delay
+        }
+    }
+
+    pub fn new(model: LatencyModel, jitter: u32, positions: Vec<Position>, rng: StdRng) -> Self {
+        Self {
+            model,
+            jitter,
+            positions,
+            rng
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    #[test]
+    fn latency_models_round_trip() {
+        assert_eq!("fixed:1".parse::<LatencyModel>(), Ok(LatencyModel::default()));
+        assert_eq!("uniform:1:4".parse::<LatencyModel>().unwrap().to_string(), "uniform:1:4");
+        assert_eq!("exponential:2.5".parse::<LatencyModel>(), Ok(LatencyModel::Exponential { mean: 2.5 }));
+        assert!("fixed:0".parse::<LatencyModel>().is_err());
+        assert!("uniform:3:2".parse::<LatencyModel>().is_err());
+        assert!("distance".parse::<LatencyModel>().is_err());
+    }
+
+    #[test]
+    fn rejects_delays_beyond_the_cap() {
+        assert_eq!("fixed:1000000".parse(), Ok(LatencyModel::Fixed { ticks: MAX_DELAY }));
+        assert!("fixed:4294967295".parse::<LatencyModel>().is_err());
+        assert!("uniform:1:1000001".parse::<LatencyModel>().is_err());
+        assert!("exponential:2e6".parse::<LatencyModel>().is_err());
+    }
+
+    #[test]
+    fn caps_sampled_delays() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(LatencyModel::Distance { ticks_per_unit: 1e9 }.sample(1e9, &mut rng), MAX_DELAY);
+        assert_eq!(LatencyModel::Distance { ticks_per_unit: 2.0 }.sample(0.0, &mut rng), 1);
+    }
+}
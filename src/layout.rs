@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use crate::node_id::NodeId;
+use crate::obstacle::Obstacle;
+use crate::position::Position;
+
+// a one-way link of an edge list, delivering messages with probability `quality`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Link {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub quality: f64,
+}
+
+// the node positions and, for nodes listed with their own, transmission ranges. Edge lists
+// also give the links, which then replace the links formed from positions, ranges and
+// obstacles, and may leave nodes without a `node` line at the origin; `unplaced_nodes`
+// counts those.
+#[derive(Clone, Debug, Default)]
+pub struct Layout {
+    pub positions: Vec<Position>,
+    pub ranges: Vec<Option<f64>>,
+    pub links: Option<Vec<Link>>,
+    pub obstacles: Vec<Obstacle>,
+    pub unplaced_nodes: usize,
+}
+
+impl From<Vec<Position>> for Layout {
+    fn from(positions: Vec<Position>) -> Self {
+        Self {
+            positions,
+            ranges: Vec::new(),
+            links: None,
+            obstacles: Vec::new(),
+            unplaced_nodes: 0
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LayoutErrorKind {
+    Io(io::Error),
+    MissingNodeCount,
+    InvalidNodeCount(String),
+    MissingCoordinate,
+    InvalidCoordinate(String),
+    InvalidRange(String),
+    MissingNodeId,
+    InvalidNodeId(String),
+    InvalidQuality(String),
+    SelfLink,
+    InvalidLoss(String),
+    TooFewPoints,
+    UnexpectedField(String),
+    NodeCountMismatch { declared: usize, actual: usize },
+    DuplicatePosition { first_line: usize },
+    DuplicateNode { first_line: usize },
+    DuplicateLink { first_line: usize },
+}
+
+#[derive(Debug)]
+pub struct LayoutError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: LayoutErrorKind,
+}
+
+impl fmt::Display for LayoutErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutErrorKind::Io(error) => write!(f, "could not read layout: {}", error),
+            LayoutErrorKind::MissingNodeCount => write!(f, "missing node count"),
+            LayoutErrorKind::InvalidNodeCount(value) => write!(f, "invalid node count '{}'", value),
+            LayoutErrorKind::MissingCoordinate => write!(f, "missing coordinate"),
+            LayoutErrorKind::InvalidCoordinate(value) => write!(f, "invalid coordinate '{}'", value),
+            LayoutErrorKind::InvalidRange(value) => write!(f, "invalid range '{}'", value),
+            LayoutErrorKind::MissingNodeId => write!(f, "missing node id"),
+            LayoutErrorKind::InvalidNodeId(value) => write!(f, "invalid node id '{}'", value),
+            LayoutErrorKind::InvalidQuality(value) => write!(f, "invalid link quality '{}'", value),
+            LayoutErrorKind::SelfLink => write!(f, "link from a node to itself"),
+            LayoutErrorKind::InvalidLoss(value) => write!(f, "invalid obstacle loss '{}'", value),
+            LayoutErrorKind::TooFewPoints => write!(f, "obstacle needs at least two points"),
+            LayoutErrorKind::UnexpectedField(value) => write!(f, "unexpected field '{}'", value),
+            LayoutErrorKind::NodeCountMismatch { declared, actual } => write!(f, "layout declares {} nodes but lists {}", declared, actual),
+            LayoutErrorKind::DuplicatePosition { first_line } => write!(f, "duplicate position, first listed on line {}", first_line),
+            LayoutErrorKind::DuplicateNode { first_line } => write!(f, "duplicate node, first listed on line {}", first_line),
+            LayoutErrorKind::DuplicateLink { first_line } => write!(f, "duplicate link, first listed on line {}", first_line),
+        }
+    }
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            LayoutErrorKind::Io(_) => write!(f, "{}", self.kind),
+            _ => write!(f, "line {}, column {}: {}", self.line, self.column, self.kind),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl LayoutError {
+    fn new(line: usize, column: usize, kind: LayoutErrorKind) -> Self {
+        Self {
+            line,
+            column,
+            kind
+        }
+    }
+}
+
+struct Field<'a> {
+    value: &'a str,
+    column: usize,
+}
+
+fn fields(line: &str) -> Vec<Field<'_>> {
+    let content = line.split('#').next().unwrap_or_default();
+
+    if content.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mut fields = Vec::new();
+    let mut start = 0;
+
+    for value in content.split(',') {
+        let leading = value.len() - value.trim_start().len();
+
+        fields.push(Field {
+            value: value.trim(),
+            column: content[..start + leading].chars().count() + 1,
+        });
+
+        start += value.len() + 1;
+    }
+
+    fields
+}
+
+fn parse_coordinate(line: usize, field: Option<&Field>, end_column: usize) -> Result<f64, LayoutError> {
+    let field = match field {
+        Some(field) if !field.value.is_empty() => field,
+        Some(field) => return Err(LayoutError::new(line, field.column, LayoutErrorKind::MissingCoordinate)),
+        None => return Err(LayoutError::new(line, end_column, LayoutErrorKind::MissingCoordinate)),
+    };
+
+    match field.value.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value + 0.0),
+        _ => Err(LayoutError::new(line, field.column, LayoutErrorKind::InvalidCoordinate(field.value.to_string()))),
+    }
+}
+
+// splits an optional `;range` off the last field of a position.
+fn split_range<'a>(field: &Field<'a>) -> (Field<'a>, Option<Field<'a>>) {
+    match field.value.split_once(';') {
+        Some((value, range)) => {
+            let leading = range.len() - range.trim_start().len();
+
+            (
+                Field { value: value.trim_end(), column: field.column },
+                Some(Field { value: range.trim(), column: field.column + value.chars().count() + 1 + leading })
+            )
+        },
+        None => (Field { value: field.value, column: field.column }, None)
+    }
+}
+
+fn parse_range(line: usize, field: &Field) -> Result<f64, LayoutError> {
+    match field.value.parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value + 0.0),
+        _ => Err(LayoutError::new(line, field.column, LayoutErrorKind::InvalidRange(field.value.to_string()))),
+    }
+}
+
+pub fn parse(contents: &str) -> Result<Layout, LayoutError> {
+    let mut declared_count: Option<(usize, usize, usize)> = None;
+    let mut positions: Vec<Position> = Vec::new();
+    let mut ranges: Vec<Option<f64>> = Vec::new();
+    let mut position_lines: HashMap<(u64, u64, Option<u64>), usize> = HashMap::new();
+    let mut obstacles: Vec<Obstacle> = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let fields = fields(line);
+
+        if fields.is_empty() {
+            continue;
+        }
+
+        let words = words(line);
+
+        if declared_count.is_some() && words[0].value == "obstacle" {
+            obstacles.push(parse_obstacle(line_number, &words, line.chars().count() + 1)?);
+
+            continue;
+        }
+
+        if declared_count.is_none() {
+            let field = &fields[0];
+
+            // an empty layout has no node to detect events or send queries.
+            let count = field.value
+                .parse::<usize>()
+                .ok()
+                .filter(|count| *count > 0)
+                .ok_or_else(|| LayoutError::new(line_number, field.column, LayoutErrorKind::InvalidNodeCount(field.value.to_string())))?;
+
+            if let Some(field) = fields.get(1) {
+                return Err(LayoutError::new(line_number, field.column, LayoutErrorKind::UnexpectedField(field.value.to_string())));
+            }
+
+            declared_count = Some((count, line_number, field.column));
+
+            continue;
+        }
+
+        let end_column = line.chars().count() + 1;
+
+        let mut fields = fields;
+        let range = match fields.pop() {
+            Some(field) => {
+                let (field, range) = split_range(&field);
+
+                fields.push(field);
+                range
+            },
+            None => None
+        };
+
+        let x = parse_coordinate(line_number, fields.first(), end_column)?;
+        let y = parse_coordinate(line_number, fields.get(1), end_column)?;
+        let z = match fields.get(2) {
+            Some(field) => Some(parse_coordinate(line_number, Some(field), end_column)?),
+            None => None
+        };
+
+        if let Some(field) = fields.get(3) {
+            return Err(LayoutError::new(line_number, field.column, LayoutErrorKind::UnexpectedField(field.value.to_string())));
+        }
+
+        let key = (x.to_bits(), y.to_bits(), z.filter(|z| *z != 0.0).map(f64::to_bits));
+
+        if let Some(first_line) = position_lines.insert(key, line_number) {
+            return Err(LayoutError::new(line_number, fields[0].column, LayoutErrorKind::DuplicatePosition { first_line }));
+        }
+
+        positions.push(Position { x, y, z });
+        ranges.push(range.map(|field| parse_range(line_number, &field)).transpose()?);
+    }
+
+    let (declared, line, column) = declared_count.ok_or_else(|| LayoutError::new(1, 1, LayoutErrorKind::MissingNodeCount))?;
+
+    if declared != positions.len() {
+        return Err(LayoutError::new(line, column, LayoutErrorKind::NodeCountMismatch { declared, actual: positions.len() }));
+    }
+
+    Ok(Layout { positions, ranges, links: None, obstacles, unplaced_nodes: 0 })
+}
+
+fn words(line: &str) -> Vec<Field<'_>> {
+    let content = line.split('#').next().unwrap_or_default();
+    let mut words = Vec::new();
+    let mut start = None;
+
+    for (column, (index, character)) in content.char_indices().chain([(content.len(), ' ')]).enumerate() {
+        match start {
+            Some((start_index, start_column)) if character.is_whitespace() => {
+                words.push(Field {
+                    value: &content[start_index..index],
+                    column: start_column,
+                });
+
+                start = None;
+            },
+            None if !character.is_whitespace() => start = Some((index, column + 1)),
+            _ => {}
+        }
+    }
+
+    words
+}
+
+fn parse_node_id(line: usize, field: Option<&Field>, count: usize, end_column: usize) -> Result<NodeId, LayoutError> {
+    let field = field.ok_or_else(|| LayoutError::new(line, end_column, LayoutErrorKind::MissingNodeId))?;
+
+    match field.value.parse::<usize>() {
+        Ok(index) if index < count => Ok(NodeId::new(index)),
+        _ => Err(LayoutError::new(line, field.column, LayoutErrorKind::InvalidNodeId(field.value.to_string()))),
+    }
+}
+
+fn parse_quality(line: usize, field: Option<&Field>) -> Result<f64, LayoutError> {
+    let Some(field) = field else {
+        return Ok(1.0);
+    };
+
+    match field.value.parse::<f64>() {
+        Ok(value) if value > 0.0 && value <= 1.0 => Ok(value),
+        _ => Err(LayoutError::new(line, field.column, LayoutErrorKind::InvalidQuality(field.value.to_string()))),
+    }
+}
+
+// `obstacle x,y x,y [x,y ...] [loss]` declares a wall, or a polygon when given three or more points.
+fn parse_obstacle(line: usize, words: &[Field], end_column: usize) -> Result<Obstacle, LayoutError> {
+    let mut points = Vec::new();
+    let mut loss = None;
+
+    for word in &words[1..] {
+        if loss.is_some() {
+            return Err(LayoutError::new(line, word.column, LayoutErrorKind::UnexpectedField(word.value.to_string())));
+        }
+
+        if !word.value.contains(',') {
+            match word.value.parse::<f64>() {
+                Ok(value) if (0.0..=1.0).contains(&value) => loss = Some(value),
+                _ => return Err(LayoutError::new(line, word.column, LayoutErrorKind::InvalidLoss(word.value.to_string()))),
+            }
+
+            continue;
+        }
+
+        let coordinates: Vec<Field> = fields(word.value)
+            .into_iter()
+            .map(|field| Field { value: field.value, column: word.column + field.column - 1 })
+            .collect();
+
+        let x = parse_coordinate(line, coordinates.first(), end_column)?;
+        let y = parse_coordinate(line, coordinates.get(1), end_column)?;
+
+        if let Some(field) = coordinates.get(2) {
+            return Err(LayoutError::new(line, field.column, LayoutErrorKind::UnexpectedField(field.value.to_string())));
+        }
+
+        points.push([x, y]);
+    }
+
+    if points.len() < 2 {
+        return Err(LayoutError::new(line, words[0].column, LayoutErrorKind::TooFewPoints));
+    }
+
+    Ok(Obstacle::new(points, loss.unwrap_or(1.0)))
+}
+
+// reads an edge list: the node count, then `node <id> x,y[,z]` lines placing nodes and
+// `edge <id> <id> [quality]` or `arc <from> <to> [quality]` lines listing undirected and
+// directed links. Nodes are numbered from 0 and nodes without a position sit at the origin.
+pub fn parse_edge_list(contents: &str) -> Result<Layout, LayoutError> {
+    let mut count: Option<usize> = None;
+    let mut positions: Vec<Position> = Vec::new();
+    let mut node_lines: HashMap<NodeId, usize> = HashMap::new();
+    let mut links: Vec<Link> = Vec::new();
+    let mut link_lines: HashMap<(NodeId, NodeId), usize> = HashMap::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let words = words(line);
+
+        if words.is_empty() {
+            continue;
+        }
+
+        let Some(count) = count else {
+            let field = &words[0];
+
+            let declared = field.value
+                .parse::<usize>()
+                .ok()
+                .filter(|count| *count > 0)
+                .ok_or_else(|| LayoutError::new(line_number, field.column, LayoutErrorKind::InvalidNodeCount(field.value.to_string())))?;
+
+            if let Some(field) = words.get(1) {
+                return Err(LayoutError::new(line_number, field.column, LayoutErrorKind::UnexpectedField(field.value.to_string())));
+            }
+
+            count = Some(declared);
+            positions = vec![Position::new(0.0, 0.0); declared];
+
+            continue;
+        };
+
+        let end_column = line.chars().count() + 1;
+        let keyword = &words[0];
+
+        let extra = match keyword.value {
+            "node" => {
+                let node_id = parse_node_id(line_number, words.get(1), count, end_column)?;
+
+                let coordinates: Vec<Field> = match words.get(2) {
+                    Some(word) => fields(word.value)
+                        .into_iter()
+                        .map(|field| Field { value: field.value, column: word.column + field.column - 1 })
+                        .collect(),
+                    None => Vec::new(),
+                };
+
+                let x = parse_coordinate(line_number, coordinates.first(), end_column)?;
+                let y = parse_coordinate(line_number, coordinates.get(1), end_column)?;
+                let z = match coordinates.get(2) {
+                    Some(field) => Some(parse_coordinate(line_number, Some(field), end_column)?),
+                    None => None
+                };
+
+                if let Some(field) = coordinates.get(3) {
+                    return Err(LayoutError::new(line_number, field.column, LayoutErrorKind::UnexpectedField(field.value.to_string())));
+                }
+
+                if let Some(first_line) = node_lines.insert(node_id, line_number) {
+                    return Err(LayoutError::new(line_number, words[1].column, LayoutErrorKind::DuplicateNode { first_line }));
+                }
+
+                positions[node_id.index()] = Position { x, y, z };
+
+                words.get(3)
+            },
+            "edge" | "arc" => {
+                let from = parse_node_id(line_number, words.get(1), count, end_column)?;
+                let to = parse_node_id(line_number, words.get(2), count, end_column)?;
+                let quality = parse_quality(line_number, words.get(3))?;
+
+                if from == to {
+                    return Err(LayoutError::new(line_number, words[2].column, LayoutErrorKind::SelfLink));
+                }
+
+                let directions = if keyword.value == "edge" { vec![(from, to), (to, from)] } else { vec![(from, to)] };
+
+                for (from, to) in directions {
+                    if let Some(first_line) = link_lines.insert((from, to), line_number) {
+                        return Err(LayoutError::new(line_number, keyword.column, LayoutErrorKind::DuplicateLink { first_line }));
+                    }
+
+                    links.push(Link { from, to, quality });
+                }
+
+                words.get(4)
+            },
+            _ => Some(keyword)
+        };
+
+        if let Some(field) = extra {
+            return Err(LayoutError::new(line_number, field.column, LayoutErrorKind::UnexpectedField(field.value.to_string())));
+        }
+    }
+
+    if count.is_none() {
+        return Err(LayoutError::new(1, 1, LayoutErrorKind::MissingNodeCount));
+    }
+
+    let unplaced_nodes = positions.len() - node_lines.len();
+
+    Ok(Layout { positions, ranges: Vec::new(), links: Some(links), obstacles: Vec::new(), unplaced_nodes })
+}
+
+// files ending in `.edges` are read as edge lists, anything else as positions.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Layout, LayoutError> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .map_err(|error| LayoutError::new(0, 0, LayoutErrorKind::Io(error)))?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("edges") => parse_edge_list(&contents),
+        _ => parse(&contents),
+    }
+}
+
+pub fn write<W: Write>(mut writer: W, positions: &[Position]) -> io::Result<()> {
+    writeln!(writer, "{}", positions.len())?;
+
+    for position in positions {
+        match position.z {
+            Some(z) => writeln!(writer, "{},{},{}", position.x, position.y, z)?,
+            None => writeln!(writer, "{},{}", position.x, position.y)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(contents: &str) -> (usize, usize, LayoutErrorKind) {
+        let error = parse(contents).unwrap_err();
+
+        (error.line, error.column, error.kind)
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let layout = parse("# two nodes\n\n2 # count\n0,0\n\n  # indented comment\n10.5, -3,2;25 # third axis and range\n").unwrap();
+
+        assert_eq!(layout.positions, vec![Position::new(0.0, 0.0), Position::new_3d(10.5, -3.0, 2.0)]);
+        assert_eq!(layout.ranges, vec![None, Some(25.0)]);
+    }
+
+    #[test]
+    fn rejects_a_count_mismatch_at_the_count() {
+        assert!(matches!(error("# header\n  3\n0,0\n1,1\n"), (2, 3, LayoutErrorKind::NodeCountMismatch { declared: 3, actual: 2 })));
+    }
+
+    #[test]
+    fn rejects_an_empty_layout() {
+        assert!(matches!(error("0\n"), (1, 1, LayoutErrorKind::InvalidNodeCount(_))));
+        assert!(matches!(parse_edge_list("0\n").unwrap_err().kind, LayoutErrorKind::InvalidNodeCount(_)));
+        assert!(matches!(error("# only a comment\n"), (1, 1, LayoutErrorKind::MissingNodeCount)));
+    }
+
+    #[test]
+    fn rejects_duplicate_positions() {
+        assert!(matches!(error("3\n0,0\n1,1\n 0.0,0\n"), (4, 2, LayoutErrorKind::DuplicatePosition { first_line: 2 })));
+        assert!(matches!(error("2\n0,0\n0,0,0\n"), (3, 1, LayoutErrorKind::DuplicatePosition { first_line: 2 })));
+        assert!(parse("2\n0,0\n0,0,1\n").is_ok());
+    }
+
+    #[test]
+    fn locates_errors_by_line_and_column() {
+        assert!(matches!(error("1\n0, x\n"), (2, 4, LayoutErrorKind::InvalidCoordinate(value)) if value == "x"));
+        assert!(matches!(error("1\n0\n"), (2, 2, LayoutErrorKind::MissingCoordinate)));
+        assert!(matches!(error("1\n0,0,0,0\n"), (2, 7, LayoutErrorKind::UnexpectedField(_))));
+        assert!(matches!(error("1\n0,0; -1\n"), (2, 6, LayoutErrorKind::InvalidRange(_))));
+        assert_eq!(parse("1\n0,x\n").unwrap_err().to_string(), "line 2, column 3: invalid coordinate 'x'");
+    }
+
+    fn edge_list_error(contents: &str) -> (usize, usize, LayoutErrorKind) {
+        let error = parse_edge_list(contents).unwrap_err();
+
+        (error.line, error.column, error.kind)
+    }
+
+    fn link(from: usize, to: usize, quality: f64) -> Link {
+        Link { from: NodeId::new(from), to: NodeId::new(to), quality }
+    }
+
+    #[test]
+    fn parses_nodes_edges_and_arcs() {
+        let layout = parse_edge_list("# a triangle\n4\nnode 0 0,0\nnode 2 5,-1,2 # raised\n\nedge 0 1\nedge 1 2 0.8\narc 2 3 0.25\n").unwrap();
+
+        assert_eq!(
+            layout.positions,
+            vec![Position::new(0.0, 0.0), Position::new(0.0, 0.0), Position::new_3d(5.0, -1.0, 2.0), Position::new(0.0, 0.0)]
+        );
+        assert_eq!(layout.unplaced_nodes, 2);
+        assert_eq!(
+            layout.links,
+            Some(vec![link(0, 1, 1.0), link(1, 0, 1.0), link(1, 2, 0.8), link(2, 1, 0.8), link(2, 3, 0.25)])
+        );
+        assert!(layout.ranges.is_empty() && layout.obstacles.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_edge_list_lines() {
+        assert!(matches!(edge_list_error("2\nedge 0 2\n"), (2, 8, LayoutErrorKind::InvalidNodeId(value)) if value == "2"));
+        assert!(matches!(edge_list_error("2\narc 0\n"), (2, 6, LayoutErrorKind::MissingNodeId)));
+        assert!(matches!(edge_list_error("2\nedge 1 1\n"), (2, 8, LayoutErrorKind::SelfLink)));
+        assert!(matches!(edge_list_error("2\nedge 0 1 0\n"), (2, 10, LayoutErrorKind::InvalidQuality(_))));
+        assert!(matches!(edge_list_error("2\nedge 0 1 1.5\n"), (2, 10, LayoutErrorKind::InvalidQuality(_))));
+        assert!(matches!(edge_list_error("2\nedge 0 1 1 x\n"), (2, 12, LayoutErrorKind::UnexpectedField(_))));
+        assert!(matches!(edge_list_error("2\nnode 0 1\n"), (2, 9, LayoutErrorKind::MissingCoordinate)));
+        assert!(matches!(edge_list_error("2\nlink 0 1\n"), (2, 1, LayoutErrorKind::UnexpectedField(value)) if value == "link"));
+        assert!(matches!(edge_list_error("# nothing\n"), (1, 1, LayoutErrorKind::MissingNodeCount)));
+    }
+
+    #[test]
+    fn rejects_duplicate_nodes_and_links() {
+        assert!(matches!(edge_list_error("2\nnode 1 0,0\nnode 1 1,1\n"), (3, 6, LayoutErrorKind::DuplicateNode { first_line: 2 })));
+        assert!(matches!(edge_list_error("2\narc 0 1\nedge 1 0\n"), (3, 1, LayoutErrorKind::DuplicateLink { first_line: 2 })));
+        assert!(parse_edge_list("2\narc 0 1\narc 1 0\n").is_ok());
+    }
+}
@@ -0,0 +1,31 @@
+pub mod position;
+pub mod route;
+pub mod node;
+pub mod node_id;
+pub mod event;
+pub mod messages;
+pub mod sensornetwork;
+pub mod network;
+pub mod channel;
+pub mod latency;
+pub mod mailbox;
+pub mod outbox;
+pub mod simulation;
+pub mod scheduler;
+pub mod metrics;
+pub mod export;
+pub mod trace;
+pub mod analysis;
+pub mod sweep;
+pub mod config;
+pub mod layout;
+pub mod topology;
+pub mod spatial_index;
+pub mod neighbours;
+pub mod obstacle;
+
+pub use crate::node_id::NodeId;
+pub use crate::layout::Layout;
+pub use crate::position::Position;
+pub use crate::sensornetwork::SensorNetworkOptions;
+pub use crate::simulation::{Simulation, SimulationBuilder};
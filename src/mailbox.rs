@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::messages::message::{Message, MessageKind};
+use crate::node_id::NodeId;
+
+// `Fifo` hands out messages in arrival order, `Priority` prefers the kinds in the order
+// listed (unlisted kinds come last) and falls back to arrival order within a kind.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum MailboxOrder {
+    #[default]
+    Fifo,
+    Priority(Vec<MessageKind>),
+}
+
+impl MailboxOrder {
+    fn rank(&self, kind: MessageKind) -> usize {
+        match self {
+            MailboxOrder::Fifo => 0,
+            MailboxOrder::Priority(kinds) => kinds
+                .iter()
+                .position(|other| *other == kind)
+                .unwrap_or(kinds.len()),
+        }
+    }
+}
+
+impl FromStr for MailboxOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.trim() == "fifo" {
+            return Ok(MailboxOrder::Fifo);
+        }
+
+        let mut kinds = Vec::new();
+
+        for name in value.split(',') {
+            let kind = match name.trim() {
+                "agent" => MessageKind::Agent,
+                "request" => MessageKind::Request,
+                "response" => MessageKind::Response,
+                name => return Err(format!("expected 'fifo' or a list of agent, request and response, got '{}'", name)),
+            };
+
+            if kinds.contains(&kind) {
+                return Err(format!("'{}' is listed twice", kind.as_str()));
+            }
+
+            kinds.push(kind);
+        }
+
+        Ok(MailboxOrder::Priority(kinds))
+    }
+}
+
+impl TryFrom<String> for MailboxOrder {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<MailboxOrder> for String {
+    fn from(order: MailboxOrder) -> Self {
+        order.to_string()
+    }
+}
+
+impl fmt::Display for MailboxOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MailboxOrder::Fifo => write!(f, "fifo"),
+            MailboxOrder::Priority(kinds) => {
+                let names: Vec<&str> = kinds.iter().map(|kind| kind.as_str()).collect();
+
+                write!(f, "{}", names.join(","))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    #[default]
+    Tail,
+    Oldest,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MailboxOptions {
+    pub order: MailboxOrder,
+    pub capacity: Option<usize>,
+    pub drop_policy: DropPolicy,
+}
+
+#[derive(Default)]
+pub struct Mailbox {
+    messages: VecDeque<(NodeId, Message)>,
+}
+
+impl Mailbox {
+    // queues the message and returns whichever message had to make room for it when the
+    // mailbox is full: the incoming one under `Tail`, the oldest queued one under `Oldest`.
+    pub fn push(&mut self, sender_id: NodeId, message: Message, options: &MailboxOptions) -> Option<(NodeId, Message)> {
+        if options.capacity.is_some_and(|capacity| self.messages.len() >= capacity) {
+            match options.drop_policy {
+                DropPolicy::Tail => return Some((sender_id, message)),
+                DropPolicy::Oldest => {
+                    let dropped = self.messages.pop_front();
+
+                    self.messages.push_back((sender_id, message));
+
+                    return dropped;
+                }
+            }
+        }
+
+        self.messages.push_back((sender_id, message));
+
+        None
+    }
+
+    // takes the best message under `order` among those whose tick has arrived; messages
+    // scheduled for a later tick stay queued without blocking the ones behind them.
+    pub fn pop(&mut self, tick: u32, order: &MailboxOrder) -> Option<(NodeId, Message)> {
+        let (index, _) = self.messages
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, message))| message.tick() <= tick)
+            .min_by_key(|(index, (_, message))| (order.rank(message.kind()), *index))?;
+
+        self.messages.remove(index)
+    }
+
+    pub fn next_tick(&self) -> Option<u32> {
+        self.messages.iter().map(|(_, message)| message.tick()).min()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = &(NodeId, Message)> {
+        self.messages.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mailbox_order_round_trips() {
+        assert_eq!("fifo".parse::<MailboxOrder>(), Ok(MailboxOrder::Fifo));
+        assert_eq!("response,agent".parse::<MailboxOrder>().unwrap().to_string(), "response,agent");
+        assert!("agent,agent".parse::<MailboxOrder>().is_err());
+        assert!("urgent".parse::<MailboxOrder>().is_err());
+    }
+}
@@ -1,88 +1,472 @@
-mod position;
-mod route;
-mod node;
-mod event;
-mod messages;
-mod sensornetwork;
-mod network;
-
-use std::fs;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::thread;
 use std::time::Instant;
-use clap::Parser;
-use crate::position::Position;
-use crate::sensornetwork::{SensorNetwork, SensorNetworkOptions};
+use std::process;
+use clap::{Parser, Subcommand, ValueEnum};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rust_rumor_routing::{SensorNetworkOptions, SimulationBuilder};
+use rust_rumor_routing::analysis::TraceAnalysis;
+use rust_rumor_routing::layout::{self, Layout};
+use rust_rumor_routing::channel::ChannelModel;
+use rust_rumor_routing::latency::LatencyModel;
+use rust_rumor_routing::neighbours::NeighbourModel;
+use rust_rumor_routing::mailbox::{DropPolicy, MailboxOrder};
+use rust_rumor_routing::scheduler::Engine;
+use rust_rumor_routing::config::ScenarioConfig;
+use rust_rumor_routing::export::{OutputFormat, RunConfig, RunResults};
+use rust_rumor_routing::sweep::{Sweep, SweepParameter};
+use rust_rumor_routing::topology::{self, Area, Topology};
 
-#[derive(Parser, Clone)]
-pub struct Args {
-    #[clap(long, default_value_t = 10000)]
-    pub event_probability: u32,
-    #[clap(long, default_value_t = 2)]
-    pub agent_probability: u32,
-    #[clap(long, default_value_t = 50)]
-    pub agent_max_hops: u32,
-    #[clap(long, default_value_t = 400)]
-    pub request_ticks: u32,
-    #[clap(long, default_value_t = 45)]
-    pub request_max_hops: u32,
-    #[clap(long, default_value_t = 8)]
-    pub request_retry_multiplier: u32,
-    #[clap(long, default_value_t = 15.0)]
-    pub neighbour_range: f64,
-    #[clap(long, default_value_t = 10000)]
-    pub iterations: u32,
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    Analyze(AnalyzeArgs),
+    Sweep(Box<SweepArgs>),
+    Generate(GenerateArgs),
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum TopologyKind {
+    Grid,
+    Hex,
+    Uniform,
+    PoissonDisk,
+    Clustered,
+    PerturbedGrid,
+}
+
+#[derive(clap::Args, Clone)]
+pub struct GenerateArgs {
+    #[clap(value_enum)]
+    pub topology: TopologyKind,
+    #[clap(long)]
+    pub nodes: usize,
+    #[clap(long)]
+    pub width: f64,
+    #[clap(long)]
+    pub height: f64,
+    #[clap(long, default_value_t = 0)]
+    pub seed: u64,
+    #[clap(long, default_value_t = 5)]
+    pub clusters: usize,
+    #[clap(long, default_value_t = 20.0)]
+    pub spread: f64,
+    #[clap(long, default_value_t = 0.25)]
+    pub jitter: f64,
+    #[clap(long)]
+    pub output_file: Option<String>,
+}
+
+#[derive(clap::Args, Clone)]
+pub struct AnalyzeArgs {
+    #[clap(long, default_value_t = 10)]
+    pub top: usize,
+    #[clap()]
+    pub trace_file_path: String,
+}
+
+#[derive(clap::Args, Clone)]
+pub struct SweepArgs {
+    #[command(flatten)]
+    pub options: OptionsArgs,
+    #[clap(long = "param", required = true)]
+    pub parameters: Vec<SweepParameter>,
+    #[clap(long, default_value_t = 5, value_parser = parse_replicates)]
+    pub replicates: usize,
+    #[clap(long, help = "[default: 10000]")]
+    pub iterations: Option<u32>,
+    #[clap(long, help = "[default: 0]")]
+    pub seed: Option<u64>,
+    #[clap(long)]
+    pub threads: Option<usize>,
+    #[clap(long)]
+    pub output_file: Option<String>,
+    #[clap(long)]
+    pub config: Option<String>,
     #[clap()]
-    pub layout_file_path: String,
+    pub layout_file_path: Option<String>,
+}
+
+#[derive(clap::Args, Clone)]
+pub struct OptionsArgs {
+    #[clap(long, help = "[default: 10000]")]
+    pub event_probability: Option<u32>,
+    #[clap(long, help = "[default: 2]")]
+    pub agent_probability: Option<u32>,
+    #[clap(long, help = "[default: 50]")]
+    pub agent_max_hops: Option<u32>,
+    #[clap(long, help = "[default: 400]")]
+    pub request_ticks: Option<u32>,
+    #[clap(long, help = "[default: 45]")]
+    pub request_max_hops: Option<u32>,
+    #[clap(long, help = "[default: 8]")]
+    pub request_retry_multiplier: Option<u32>,
+    #[clap(long, help = "[default: 15]")]
+    pub neighbour_range: Option<f64>,
+    #[clap(long, help = "[default: unit-disk]")]
+    pub neighbour_model: Option<NeighbourModel>,
+    #[clap(long, help = "[default: fifo]")]
+    pub mailbox_order: Option<MailboxOrder>,
+    #[clap(long, help = "[default: 0]")]
+    pub mailbox_capacity: Option<u32>,
+    #[clap(long, value_enum, help = "[default: tail]")]
+    pub drop_policy: Option<DropPolicy>,
+    #[clap(long, help = "[default: 1]")]
+    pub service_rate: Option<u32>,
+    #[clap(long, value_enum, help = "[default: tick]")]
+    pub engine: Option<Engine>,
+    #[clap(long, help = "[default: 1]")]
+    pub update_threads: Option<u32>,
+    #[clap(long, help = "[default: perfect]")]
+    pub channel: Option<ChannelModel>,
+    #[clap(long, help = "[default: fixed:1]")]
+    pub latency: Option<LatencyModel>,
+    #[clap(long, help = "[default: 0]")]
+    pub latency_jitter: Option<u32>,
+}
+
+impl OptionsArgs {
+    pub fn apply(&self, options: &mut SensorNetworkOptions) {
+        if let Some(event_probability) = self.event_probability {
+            options.event_probability = event_probability;
+        }
+
+        if let Some(agent_probability) = self.agent_probability {
+            options.agent_probability = agent_probability;
+        }
+
+        if let Some(agent_max_hops) = self.agent_max_hops {
+            options.agent_max_hops = agent_max_hops;
+        }
+
+        if let Some(request_ticks) = self.request_ticks {
+            options.request_ticks = request_ticks;
+        }
+
+        if let Some(request_max_hops) = self.request_max_hops {
+            options.request_max_hops = request_max_hops;
+        }
+
+        if let Some(request_retry_multiplier) = self.request_retry_multiplier {
+            options.request_retry_multiplier = request_retry_multiplier;
+        }
+
+        if let Some(neighbour_range) = self.neighbour_range {
+            options.neighbour_range = neighbour_range;
+        }
+
+        if let Some(neighbour_model) = self.neighbour_model {
+            options.neighbour_model = neighbour_model;
+        }
+
+        if let Some(mailbox_order) = &self.mailbox_order {
+            options.mailbox_order = mailbox_order.clone();
+        }
+
+        if let Some(mailbox_capacity) = self.mailbox_capacity {
+            options.mailbox_capacity = mailbox_capacity;
+        }
+
+        if let Some(drop_policy) = self.drop_policy {
+            options.drop_policy = drop_policy;
+        }
+
+        if let Some(service_rate) = self.service_rate {
+            options.service_rate = service_rate;
+        }
+
+        if let Some(engine) = self.engine {
+            options.engine = engine;
+        }
+
+        if let Some(update_threads) = self.update_threads {
+            options.update_threads = update_threads;
+        }
+
+        if let Some(channel) = self.channel {
+            options.channel = channel;
+        }
+
+        if let Some(latency) = self.latency {
+            options.latency = latency;
+        }
+
+        if let Some(latency_jitter) = self.latency_jitter {
+            options.latency_jitter = latency_jitter;
+        }
+    }
 }
 
+#[derive(clap::Args, Clone)]
+pub struct Args {
+    #[command(flatten)]
+    pub options: OptionsArgs,
+    #[clap(long, help = "[default: 10000]")]
+    pub iterations: Option<u32>,
+    #[clap(long)]
+    pub seed: Option<u64>,
+    #[clap(long, value_enum)]
+    pub output: Option<OutputFormat>,
+    #[clap(long)]
+    pub output_file: Option<String>,
+    #[clap(long)]
+    pub trace_file: Option<String>,
+    #[clap(long)]
+    pub config: Option<String>,
+    #[clap()]
+    pub layout_file_path: Option<String>,
+}
 
 fn main() {
-    let args = Args::parse();
-
-    let layout_contents = fs::read_to_string(args.layout_file_path)
-        .expect("could not read layout.");
-    let layout_lines = layout_contents.lines();
-
-    let mut positions: Vec<Position> = Vec::new();
-
-    for line in layout_lines.skip(1) {
-        let mut coordinates = line.split(',');
-
-        positions.push(
-            Position::new(
-                coordinates
-                    .next().unwrap()
-                    .parse().unwrap(),
-                coordinates
-                    .next().unwrap()
-                    .parse().unwrap()
-            )
-        )
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Analyze(args)) => analyze(args),
+        Some(Command::Sweep(args)) => sweep(*args),
+        Some(Command::Generate(args)) => generate(args),
+        None => run(cli.args),
     }
+}
 
-    let mut network = SensorNetwork::new(
-        positions,
-        SensorNetworkOptions {
-            event_probability: args.event_probability,
-            agent_probability: args.agent_probability,
-            agent_max_hops: args.agent_max_hops,
-            request_ticks: args.request_ticks,
-            request_max_hops: args.request_max_hops,
-            request_retry_multiplier: args.request_retry_multiplier,
-            neighbour_range: args.neighbour_range,
-        }
-    );
+fn parse_replicates(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(replicates) if replicates > 0 => Ok(replicates),
+        _ => Err(format!("expected at least one replicate, got '{}'", value)),
+    }
+}
 
-    let mut answers_received = 0;
-    
-    let iterations = args.iterations;
+fn read_layout(path: &str) -> Layout {
+    layout::load(path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    })
+}
+
+fn read_config(path: Option<&String>) -> ScenarioConfig {
+    match path {
+        Some(path) => ScenarioConfig::load(path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        }),
+        None => ScenarioConfig::default()
+    }
+}
+
+fn generate(args: GenerateArgs) {
+    let topology = match args.topology {
+        TopologyKind::Grid => Topology::Grid,
+        TopologyKind::Hex => Topology::HexGrid,
+        TopologyKind::Uniform => Topology::Uniform,
+        TopologyKind::PoissonDisk => Topology::PoissonDisk,
+        TopologyKind::Clustered => Topology::Clustered { clusters: args.clusters, spread: args.spread },
+        TopologyKind::PerturbedGrid => Topology::PerturbedGrid { jitter: args.jitter },
+    };
+
+    let area = Area {
+        width: args.width,
+        height: args.height,
+    };
+
+    let positions = topology::generate(topology, area, args.nodes, &mut StdRng::seed_from_u64(args.seed))
+        .unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+
+    let mut writer: Box<dyn Write> = match &args.output_file {
+        Some(path) => Box::new(BufWriter::new(File::create(path).expect("could not create output file."))),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    layout::write(&mut writer, &positions)
+        .and_then(|_| writer.flush())
+        .expect("could not write layout.");
+}
+
+fn analyze(args: AnalyzeArgs) {
+    let analysis = File::open(&args.trace_file_path)
+        .and_then(|file| TraceAnalysis::from_reader(BufReader::new(file)))
+        .unwrap_or_else(|error| {
+            eprintln!("{}: {}", args.trace_file_path, error);
+            process::exit(1);
+        });
+
+    println!("{}", analysis.report(args.top));
+}
+
+fn sweep(args: SweepArgs) {
+    let config = read_config(args.config.as_ref());
+
+    let layout_file_path = args.layout_file_path
+        .or(config.layout_file_path)
+        .expect("layout file path is required.");
+
+    let mut options = config.options;
+    args.options.apply(&mut options);
+
+    let mut layout = read_layout(&layout_file_path);
+    layout.obstacles.extend(config.obstacles);
+
+    let sweep = Sweep {
+        layout,
+        options,
+        parameters: args.parameters,
+        replicates: args.replicates,
+        iterations: args.iterations.or(config.iterations).unwrap_or(10000),
+        seed: args.seed.or(config.seed).unwrap_or(0),
+        threads: args.threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get())),
+    };
+
+    eprintln!("running {} points with {} replicates.", sweep.points().len(), sweep.replicates);
 
-    println!("running {} iterations.", iterations);
-    
     let now: Instant = Instant::now();
-    for _ in 0..iterations {
-        answers_received += network.update();
+    let points = sweep.run().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let elapsed = now.elapsed();
+
+    eprintln!("done in {:?}.", elapsed);
+
+    let mut writer: Box<dyn Write> = match &args.output_file {
+        Some(path) => Box::new(BufWriter::new(File::create(path).expect("could not create output file."))),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    sweep.write_csv(&points, &mut writer)
+        .and_then(|_| writer.flush())
+        .expect("could not write results.");
+}
+
+fn run(args: Args) {
+    let scenario = read_config(args.config.as_ref());
+
+    let layout_file_path = args.layout_file_path
+        .or(scenario.layout_file_path)
+        .expect("layout file path is required.");
+
+    let mut layout = read_layout(&layout_file_path);
+    layout.obstacles.extend(scenario.obstacles);
+
+    let mut options = scenario.options;
+    args.options.apply(&mut options);
+
+    if let Err(error) = options.validate().and_then(|_| options.validate_layout(&layout)) {
+        eprintln!("{}", error);
+        process::exit(1);
     }
+
+    let config = RunConfig {
+        config_file_path: args.config,
+        layout_file_path: Some(layout_file_path),
+        nodes: layout.positions.len(),
+        obstacles: layout.obstacles.clone(),
+        seed: args.seed.or(scenario.seed).unwrap_or_else(rand::random),
+        iterations: args.iterations.or(scenario.iterations).unwrap_or(10000),
+        options,
+        output: args.output.or(scenario.output),
+        output_file: args.output_file.or(scenario.output_file),
+        trace_file: args.trace_file.or(scenario.trace_file),
+    };
+
+    if config.output_file.is_some() && config.output.is_none() {
+        eprintln!("--output-file needs an output format, set --output or `output` in the config file.");
+        process::exit(1);
+    }
+
+    // results written to standard output must stay parseable, so the report goes to standard error then.
+    let mut log: Box<dyn Write> = if config.output.is_some() && config.output_file.is_none() {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    };
+
+    writeln!(log, "running with config: {:?}", &config).expect("could not write report.");
+
+    let mut builder = SimulationBuilder::new(layout, config.options.clone())
+        .seed(config.seed);
+
+    if let Some(path) = &config.trace_file {
+        builder = builder.trace(Box::new(BufWriter::new(File::create(path).expect("could not create trace file."))));
+    }
+
+    let mut simulation = builder.build();
+
+    let isolated_nodes = simulation.isolated_nodes();
+
+    if !isolated_nodes.is_empty() {
+        let listed: Vec<String> = isolated_nodes.iter().take(10).map(|node_id| node_id.to_string()).collect();
+
+        eprintln!(
+            "warning: {} of {} nodes have no neighbours and cannot forward messages: {}{}",
+            isolated_nodes.len(),
+            simulation.nodes().count(),
+            listed.join(", "),
+            if isolated_nodes.len() > listed.len() { ", ..." } else { "" }
+        );
+    }
+
+    writeln!(log, "running {} iterations.", config.iterations).expect("could not write report.");
+    
+    let now: Instant = Instant::now();
+    let answers_received = simulation.run(config.iterations);
     let elapsed = now.elapsed();
 
-    println!("done in {:?}, received {} answers.", elapsed, answers_received);
+    simulation.finish_trace().expect("could not write trace.");
+
+    writeln!(log, "done in {:?}, received {} answers.", elapsed, answers_received)
+        .and_then(|_| writeln!(log, "{}", simulation.query_metrics().report()))
+        .and_then(|_| writeln!(log, "{}", simulation.message_counters()))
+        .and_then(|_| writeln!(log, "{}", simulation.queue_counters()))
+        .expect("could not write report.");
+
+    if !simulation.obstructed_links().is_empty() {
+        writeln!(log, "{}", simulation.obstacle_report()).expect("could not write report.");
+    }
+
+    log.flush().expect("could not write report.");
+
+    if let Some(output) = config.output {
+        let mut writer: Box<dyn Write> = match &config.output_file {
+            Some(path) => Box::new(BufWriter::new(File::create(path).expect("could not create output file."))),
+            None => Box::new(io::stdout().lock()),
+        };
+
+        let results = RunResults::new(config, &simulation);
+
+        match output {
+            OutputFormat::Json => results.write_json(&mut writer),
+            OutputFormat::Csv => results.write_csv(&mut writer),
+        }.and_then(|_| writer.flush()).expect("could not write results.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_override_the_scenario_file() {
+        let scenario = ScenarioConfig::from_toml("seed = 42\niterations = 500\n\n[options]\nagent_max_hops = 60\nneighbour_range = 12.5\n").unwrap();
+        let cli = Cli::try_parse_from(["rust-rumor-routing", "--agent-max-hops", "70", "--seed", "1", "layout.txt"]).unwrap();
+        let mut options = scenario.options;
+
+        cli.args.options.apply(&mut options);
+
+        assert_eq!(options.agent_max_hops, 70);
+        assert_eq!(options.neighbour_range, 12.5);
+        assert_eq!(options.request_ticks, SensorNetworkOptions::default().request_ticks);
+        assert_eq!(cli.args.seed.or(scenario.seed), Some(1));
+        assert_eq!(cli.args.iterations.or(scenario.iterations), Some(500));
+    }
 }
@@ -1,20 +1,23 @@
 use std::collections::HashMap;
 use crate::route::Route;
+use crate::node_id::NodeId;
 
 #[derive(Clone)]
 pub struct AgentMessage {
     pub tick: u32,
     pub hops: u32,
-    pub route: Vec<u32>,
+    pub route: Vec<NodeId>,
+    pub id: u32,
     pub routes: HashMap<u32, Route>
 }
 
 impl AgentMessage {
-    pub fn new(tick: u32, hops: u32, route: Vec<u32>, routes: HashMap<u32, Route>) -> Self {
+    pub fn new(tick: u32, hops: u32, route: Vec<NodeId>, id: u32, routes: HashMap<u32, Route>) -> Self {
         Self {
             tick,
             hops,
             route,
+            id,
             routes
         }
     }
@@ -1,9 +1,119 @@
 use crate::messages::agent_message::AgentMessage;
 use crate::messages::request_message::RequestMessage;
 use crate::messages::response_message::ResponseMessage;
+use serde::{Deserialize, Serialize};
 
+// message sizes count only what the protocol puts on air, as 4-byte words for ids, counters
+// and coordinates and 1 byte for the route flag: agents carry their id, hop count, visited
+// nodes and routes, requests their id, hop count, sequence, event id, route flag and
+// visited nodes, and responses their id, event id, return path and the event with its
+// detection tick and position. Send ticks, `route_hops`, `request_hops` and the hop count
+// and route of responses only feed the metrics and are left out.
+const WORD: usize = 4;
+const FLAG: usize = 1;
+// event id, next node and distance.
+const ROUTE: usize = 3 * WORD;
+
+#[derive(Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Message {
     AgentMessage(AgentMessage),
     RequestMessage(RequestMessage),
     ResponseMessage(ResponseMessage)
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    Agent,
+    Request,
+    Response,
+}
+
+impl MessageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageKind::Agent => "agent",
+            MessageKind::Request => "request",
+            MessageKind::Response => "response",
+        }
+    }
+}
+
+impl Message {
+    pub fn tick(&self) -> u32 {
+        match self {
+            Message::AgentMessage(message) => message.tick,
+            Message::RequestMessage(message) => message.tick,
+            Message::ResponseMessage(message) => message.tick,
+        }
+    }
+
+    pub fn delay(&mut self, ticks: u32) {
+        match self {
+            Message::AgentMessage(message) => message.tick = message.tick.saturating_add(ticks),
+            Message::RequestMessage(message) => message.tick = message.tick.saturating_add(ticks),
+            Message::ResponseMessage(message) => message.tick = message.tick.saturating_add(ticks),
+        }
+    }
+
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Message::AgentMessage(_) => MessageKind::Agent,
+            Message::RequestMessage(_) => MessageKind::Request,
+            Message::ResponseMessage(_) => MessageKind::Response,
+        }
+    }
+
+    // estimated bytes on air, see the constants above.
+    pub fn size(&self) -> usize {
+        match self {
+            Message::AgentMessage(message) => 2 * WORD + message.route.len() * WORD + message.routes.len() * ROUTE,
+            Message::RequestMessage(message) => 4 * WORD + FLAG + message.route.len() * WORD,
+            Message::ResponseMessage(message) => {
+                let coordinates = if message.event.position.z.is_some() { 3 } else { 2 };
+                let event = 2 * WORD + coordinates * WORD;
+
+                2 * WORD + message.path.len() * WORD + event
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use crate::event::Event;
+    use crate::node_id::NodeId;
+    use crate::position::Position;
+    use crate::route::Route;
+
+    fn nodes(count: usize) -> Vec<NodeId> {
+        (0..count).map(NodeId::new).collect()
+    }
+
+    #[test]
+    fn sizes_count_only_protocol_fields() {
+        let routes: HashMap<u32, Route> = (0..2).map(|id| (id, Route::new(id, NodeId::new(1), 3))).collect();
+        let agent = Message::AgentMessage(AgentMessage::new(100, 7, nodes(3), 1, routes));
+        let request = Message::RequestMessage(RequestMessage::new(100, 5, nodes(4), 2, 2, 9, true, 3));
+        let response = |position| Message::ResponseMessage(ResponseMessage::new(100, 6, nodes(2), 2, nodes(5), 9, Event::new(9, 40, position), 5));
+
+        assert_eq!(agent.size(), 8 + 3 * 4 + 2 * 12);
+        assert_eq!(request.size(), 17 + 4 * 4);
+        assert_eq!(response(Position::new(1.0, 2.0)).size(), 8 + 5 * 4 + 16);
+        assert_eq!(response(Position::new_3d(1.0, 2.0, 3.0)).size(), 8 + 5 * 4 + 20);
+    }
+
+    #[test]
+    fn sizes_do_not_depend_on_ticks_or_metric_fields() {
+        let request = |tick, route_hops| Message::RequestMessage(RequestMessage::new(tick, 5, nodes(4), 2, 2, 9, false, route_hops));
+        let mut delayed = request(1, 0);
+
+        delayed.delay(1000);
+
+        assert_eq!(delayed.tick(), 1001);
+        assert_eq!(delayed.size(), request(1, 40).size());
+    }
+}
@@ -1,16 +1,20 @@
+use crate::node_id::NodeId;
+
 #[derive(Clone)]
 pub struct RequestMessage {
     pub tick: u32,
     pub hops: u32,
-    pub route: Vec<u32>,
+    pub route: Vec<NodeId>,
     pub id: u32,
     pub sequence: u32,
     pub event_id: u32,
     pub is_route_found: bool,
+    pub route_hops: u32,
 }
 
 impl RequestMessage {
-    pub fn new(tick: u32, hops: u32, route: Vec<u32>, id: u32, sequence: u32, event_id: u32, is_route_found: bool) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(tick: u32, hops: u32, route: Vec<NodeId>, id: u32, sequence: u32, event_id: u32, is_route_found: bool, route_hops: u32) -> Self {
         Self {
             tick,
             hops,
@@ -18,7 +22,8 @@ impl RequestMessage {
             id,
             sequence,
             event_id,
-            is_route_found
+            is_route_found,
+            route_hops
         }
     }
 }
\ No newline at end of file
@@ -1,18 +1,21 @@
 use crate::event::Event;
+use crate::node_id::NodeId;
 
 #[derive(Clone)]
 pub struct ResponseMessage {
     pub tick: u32,
     pub hops: u32,
-    pub route: Vec<u32>,
+    pub route: Vec<NodeId>,
     pub id: u32,
-    pub path: Vec<u32>,
+    pub path: Vec<NodeId>,
     pub event_id: u32,
     pub event: Event,
+    pub request_hops: u32,
 }
 
 impl ResponseMessage {
-    pub fn new(tick: u32, hops: u32, route: Vec<u32>, id: u32, path: Vec<u32>, event_id: u32, event: Event) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(tick: u32, hops: u32, route: Vec<NodeId>, id: u32, path: Vec<NodeId>, event_id: u32, event: Event, request_hops: u32) -> Self {
         Self {
             tick,
             hops,
@@ -20,7 +23,8 @@ impl ResponseMessage {
             id,
             path,
             event_id,
-            event
+            event,
+            request_hops
         }
     }
 }
\ No newline at end of file
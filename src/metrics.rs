@@ -0,0 +1,7 @@
+pub mod failure_reason;
+pub mod query_record;
+pub mod query_metrics;
+pub mod query_event;
+pub mod query_report;
+pub mod message_counters;
+pub mod queue_counters;
@@ -0,0 +1,47 @@
+use std::fmt;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    RandomWalkTtlExhausted,
+    RouteTtlExhausted,
+    ResponsePathBroken,
+    RetryBudgetExhausted,
+    EventUnknown,
+    MailboxOverflow,
+    MessageLost,
+    NoNeighbour,
+}
+
+impl FailureReason {
+    pub const ALL: [FailureReason; 8] = [
+        FailureReason::RandomWalkTtlExhausted,
+        FailureReason::RouteTtlExhausted,
+        FailureReason::ResponsePathBroken,
+        FailureReason::RetryBudgetExhausted,
+        FailureReason::EventUnknown,
+        FailureReason::MailboxOverflow,
+        FailureReason::MessageLost,
+        FailureReason::NoNeighbour,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureReason::RandomWalkTtlExhausted => "random_walk_ttl_exhausted",
+            FailureReason::RouteTtlExhausted => "route_ttl_exhausted",
+            FailureReason::ResponsePathBroken => "response_path_broken",
+            FailureReason::RetryBudgetExhausted => "retry_budget_exhausted",
+            FailureReason::EventUnknown => "event_unknown",
+            FailureReason::MailboxOverflow => "mailbox_overflow",
+            FailureReason::MessageLost => "message_lost",
+            FailureReason::NoNeighbour => "no_neighbour",
+        }
+    }
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
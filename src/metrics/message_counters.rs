@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::Serialize;
+use crate::messages::message::MessageKind;
+use crate::node_id::NodeId;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct MessageCounts {
+    pub agent: u64,
+    pub request: u64,
+    pub response: u64,
+    pub bytes: u64,
+}
+
+impl MessageCounts {
+    pub fn add(&mut self, kind: MessageKind, bytes: usize) {
+        match kind {
+            MessageKind::Agent => self.agent += 1,
+            MessageKind::Request => self.request += 1,
+            MessageKind::Response => self.response += 1,
+        }
+
+        self.bytes += bytes as u64;
+    }
+
+    pub fn transmissions(&self) -> u64 {
+        self.agent + self.request + self.response
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MessageCounters {
+    total: MessageCounts,
+    per_node: BTreeMap<NodeId, MessageCounts>,
+    per_tick: BTreeMap<u32, MessageCounts>,
+    lost: MessageCounts,
+    no_neighbour: MessageCounts,
+}
+
+impl MessageCounters {
+    pub fn record(&mut self, tick: u32, node_id: NodeId, kind: MessageKind, bytes: usize) {
+        self.total.add(kind, bytes);
+        self.per_node.entry(node_id).or_default().add(kind, bytes);
+        self.per_tick.entry(tick).or_default().add(kind, bytes);
+    }
+
+    // transmissions the channel lost in transit, they are still counted by `record`.
+    pub fn record_loss(&mut self, kind: MessageKind, bytes: usize) {
+        self.lost.add(kind, bytes);
+    }
+
+    // messages a node without neighbours could not forward, they were never sent.
+    pub fn record_no_neighbour(&mut self, kind: MessageKind, bytes: usize) {
+        self.no_neighbour.add(kind, bytes);
+    }
+
+    pub fn total(&self) -> &MessageCounts {
+        &self.total
+    }
+
+    pub fn per_node(&self) -> &BTreeMap<NodeId, MessageCounts> {
+        &self.per_node
+    }
+
+    pub fn per_tick(&self) -> &BTreeMap<u32, MessageCounts> {
+        &self.per_tick
+    }
+
+    pub fn lost(&self) -> &MessageCounts {
+        &self.lost
+    }
+
+    pub fn no_neighbour(&self) -> &MessageCounts {
+        &self.no_neighbour
+    }
+}
+
+impl fmt::Display for MessageCounters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "transmissions: {} (agent: {}, request: {}, response: {}), bytes: {}",
+            self.total.transmissions(),
+            self.total.agent,
+            self.total.request,
+            self.total.response,
+            self.total.bytes
+        )?;
+
+        let busiest_node = self.per_node
+            .iter()
+            .max_by_key(|(_, counts)| counts.transmissions());
+
+        if let Some((node_id, counts)) = busiest_node {
+            writeln!(
+                f,
+                "per active node: mean: {:.1}, max: {} (node {})",
+                self.total.transmissions() as f64 / self.per_node.len() as f64,
+                counts.transmissions(),
+                node_id
+            )?;
+        }
+
+        let busiest_tick = self.per_tick
+            .iter()
+            .max_by_key(|(_, counts)| counts.transmissions());
+
+        if let Some((tick, counts)) = busiest_tick {
+            write!(
+                f,
+                "per active tick: mean: {:.1}, max: {} (tick {})",
+                self.total.transmissions() as f64 / self.per_tick.len() as f64,
+                counts.transmissions(),
+                tick
+            )?;
+        }
+
+        if self.lost.transmissions() > 0 {
+            write!(
+                f,
+                "\nlost: {} ({:.1}%) (agent: {}, request: {}, response: {})",
+                self.lost.transmissions(),
+                100.0 * self.lost.transmissions() as f64 / self.total.transmissions() as f64,
+                self.lost.agent,
+                self.lost.request,
+                self.lost.response
+            )?;
+        }
+
+        if self.no_neighbour.transmissions() > 0 {
+            write!(
+                f,
+                "\ndropped without a neighbour: {} (agent: {}, request: {})",
+                self.no_neighbour.transmissions(),
+                self.no_neighbour.agent,
+                self.no_neighbour.request
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters() -> MessageCounters {
+        let mut counters = MessageCounters::default();
+
+        counters.record(1, NodeId(0), MessageKind::Agent, 20);
+        counters.record(1, NodeId(1), MessageKind::Request, 30);
+        counters.record(2, NodeId(1), MessageKind::Response, 40);
+        counters.record(2, NodeId(1), MessageKind::Request, 30);
+
+        counters
+    }
+
+    #[test]
+    fn counts_are_split_by_kind_node_and_tick() {
+        let counters = counters();
+
+        assert_eq!(*counters.total(), MessageCounts { agent: 1, request: 2, response: 1, bytes: 120 });
+        assert_eq!(counters.total().transmissions(), 4);
+
+        assert_eq!(counters.per_node()[&NodeId(0)], MessageCounts { agent: 1, request: 0, response: 0, bytes: 20 });
+        assert_eq!(counters.per_node()[&NodeId(1)], MessageCounts { agent: 0, request: 2, response: 1, bytes: 100 });
+
+        assert_eq!(counters.per_tick()[&1], MessageCounts { agent: 1, request: 1, response: 0, bytes: 50 });
+        assert_eq!(counters.per_tick()[&2], MessageCounts { agent: 0, request: 1, response: 1, bytes: 70 });
+    }
+
+    #[test]
+    fn losses_and_drops_are_kept_apart_from_the_totals() {
+        let mut counters = counters();
+
+        counters.record_loss(MessageKind::Request, 30);
+        counters.record_no_neighbour(MessageKind::Agent, 20);
+
+        assert_eq!(counters.total().transmissions(), 4);
+        assert_eq!(*counters.lost(), MessageCounts { agent: 0, request: 1, response: 0, bytes: 30 });
+        assert_eq!(*counters.no_neighbour(), MessageCounts { agent: 1, request: 0, response: 0, bytes: 20 });
+    }
+
+    #[test]
+    fn display_reports_the_busiest_node_and_tick() {
+        let mut counters = counters();
+
+        assert_eq!(
+            counters.to_string(),
+            "transmissions: 4 (agent: 1, request: 2, response: 1), bytes: 120\n\
+             per active node: mean: 2.0, max: 3 (node 1)\n\
+             per active tick: mean: 2.0, max: 2 (tick 2)"
+        );
+
+        counters.record_loss(MessageKind::Request, 30);
+        counters.record_no_neighbour(MessageKind::Agent, 20);
+
+        assert!(counters.to_string().ends_with(
+            "\nlost: 1 (25.0%) (agent: 0, request: 1, response: 0)\
+             \ndropped without a neighbour: 1 (agent: 1, request: 0)"
+        ));
+    }
+
+    #[test]
+    fn display_of_an_empty_run_has_only_the_totals() {
+        assert_eq!(
+            MessageCounters::default().to_string(),
+            "transmissions: 0 (agent: 0, request: 0, response: 0), bytes: 0\n"
+        );
+    }
+}
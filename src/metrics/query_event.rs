@@ -0,0 +1,11 @@
+use crate::metrics::failure_reason::FailureReason;
+use crate::node_id::NodeId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryEvent {
+    Created { id: u32, node_id: NodeId, event_id: u32, tick: u32 },
+    Retried { id: u32, sequence: u32 },
+    Answered { id: u32, tick: u32, request_hops: u32, response_hops: u32 },
+    Dropped { id: u32, reason: FailureReason },
+    Failed { id: u32, tick: u32 },
+}
@@ -0,0 +1,95 @@
+use std::collections::{BTreeMap, HashSet};
+use crate::metrics::failure_reason::FailureReason;
+use crate::metrics::query_event::QueryEvent;
+use crate::metrics::query_record::{QueryOutcome, QueryRecord};
+use crate::metrics::query_report::QueryReport;
+use crate::node_id::NodeId;
+
+#[derive(Default)]
+pub struct QueryMetrics {
+    records: BTreeMap<u32, QueryRecord>,
+    known_event_ids: HashSet<u32>,
+}
+
+impl QueryMetrics {
+    // the event has reached a node other than the one that detected it, so queries for it
+    // have a route to follow. Queries failing for an event that never got this far fail
+    // with `EventUnknown`.
+    pub fn event_spread(&mut self, event_id: u32) {
+        self.known_event_ids.insert(event_id);
+    }
+
+    pub fn is_event_known(&self, event_id: u32) -> bool {
+        self.known_event_ids.contains(&event_id)
+    }
+
+    pub fn query_created(&mut self, id: u32, node_id: NodeId, event_id: u32, tick: u32) {
+        self.records.insert(
+            id,
+            QueryRecord::new(
+                id,
+                node_id,
+                event_id,
+                tick
+            )
+        );
+    }
+
+    pub fn query_retried(&mut self, id: u32, sequence: u32) {
+        if let Some(record) = self.records.get_mut(&id) {
+            record.sequence = sequence;
+        }
+    }
+
+    pub fn query_answered(&mut self, id: u32, tick: u32, request_hops: u32, response_hops: u32) {
+        if let Some(record) = self.records.get_mut(&id) {
+            record.completed_tick = Some(tick);
+            record.request_hops = Some(request_hops);
+            record.response_hops = Some(response_hops);
+            record.outcome = QueryOutcome::Answered;
+        }
+    }
+
+    pub fn query_dropped(&mut self, id: u32, reason: FailureReason) {
+        if let Some(record) = self.records.get_mut(&id) {
+            if record.outcome == QueryOutcome::Pending {
+                record.last_drop = Some(reason);
+            }
+        }
+    }
+
+    pub fn query_failed(&mut self, id: u32, tick: u32) {
+        if let Some(record) = self.records.get_mut(&id) {
+            let reason = if !self.known_event_ids.contains(&record.event_id) {
+                FailureReason::EventUnknown
+            } else {
+                record.last_drop.unwrap_or(FailureReason::RetryBudgetExhausted)
+            };
+
+            record.completed_tick = Some(tick);
+            record.outcome = QueryOutcome::Failed(reason);
+        }
+    }
+
+    pub fn record_event(&mut self, event: QueryEvent) {
+        match event {
+            QueryEvent::Created { id, node_id, event_id, tick } => self.query_created(id, node_id, event_id, tick),
+            QueryEvent::Retried { id, sequence } => self.query_retried(id, sequence),
+            QueryEvent::Answered { id, tick, request_hops, response_hops } => self.query_answered(id, tick, request_hops, response_hops),
+            QueryEvent::Dropped { id, reason } => self.query_dropped(id, reason),
+            QueryEvent::Failed { id, tick } => self.query_failed(id, tick),
+        }
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &QueryRecord> {
+        self.records.values()
+    }
+
+    pub fn record(&self, id: u32) -> Option<&QueryRecord> {
+        self.records.get(&id)
+    }
+
+    pub fn report(&self) -> QueryReport {
+        QueryReport::new(self.records.values())
+    }
+}
@@ -0,0 +1,64 @@
+use crate::metrics::failure_reason::FailureReason;
+use crate::node_id::NodeId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryOutcome {
+    Pending,
+    Answered,
+    Failed(FailureReason),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryRecord {
+    pub id: u32,
+    pub node_id: NodeId,
+    pub event_id: u32,
+    pub created_tick: u32,
+    pub sequence: u32,
+    pub completed_tick: Option<u32>,
+    pub request_hops: Option<u32>,
+    pub response_hops: Option<u32>,
+    pub outcome: QueryOutcome,
+    pub last_drop: Option<FailureReason>,
+}
+
+impl QueryOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueryOutcome::Pending => "pending",
+            QueryOutcome::Answered => "answered",
+            QueryOutcome::Failed(_) => "failed",
+        }
+    }
+
+    pub fn failure_reason(&self) -> Option<FailureReason> {
+        match self {
+            QueryOutcome::Failed(reason) => Some(*reason),
+            _ => None
+        }
+    }
+}
+
+impl QueryRecord {
+    pub fn latency(&self) -> Option<u32> {
+        match self.outcome {
+            QueryOutcome::Answered => self.completed_tick.map(|tick| tick - self.created_tick),
+            _ => None
+        }
+    }
+
+    pub fn new(id: u32, node_id: NodeId, event_id: u32, created_tick: u32) -> Self {
+        Self {
+            id,
+            node_id,
+            event_id,
+            created_tick,
+            sequence: 1,
+            completed_tick: None,
+            request_hops: None,
+            response_hops: None,
+            outcome: QueryOutcome::Pending,
+            last_drop: None
+        }
+    }
+}
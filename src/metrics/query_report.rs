@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::Serialize;
+use crate::metrics::failure_reason::FailureReason;
+use crate::metrics::query_record::{QueryOutcome, QueryRecord};
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct QueryReport {
+    pub queries: u32,
+    pub answered: u32,
+    pub failed: u32,
+    pub pending: u32,
+    pub delivery_ratio: f64,
+    pub latency_mean: f64,
+    pub latency_p50: u32,
+    pub latency_p90: u32,
+    pub latency_p99: u32,
+    pub request_hops_mean: f64,
+    pub response_hops_mean: f64,
+    pub retries_mean: f64,
+    pub failure_reasons: BTreeMap<FailureReason, u32>,
+}
+
+fn mean(values: impl Iterator<Item = u32>) -> f64 {
+    let (sum, count) = values.fold((0u64, 0u64), |(sum, count), value| (sum + value as u64, count + 1));
+
+    if count > 0 {
+        sum as f64 / count as f64
+    } else {
+        0.0
+    }
+}
+
+fn percentile(sorted_values: &[u32], percentile: f64) -> u32 {
+    if sorted_values.is_empty() {
+        return 0;
+    }
+
+    let rank = (percentile / 100.0 * sorted_values.len() as f64).ceil() as usize;
+
+    sorted_values[rank.clamp(1, sorted_values.len()) - 1]
+}
+
+impl QueryReport {
+    pub fn new<'a>(records: impl Iterator<Item = &'a QueryRecord>) -> Self {
+        let records: Vec<&QueryRecord> = records.collect();
+
+        let count = |outcome| records.iter().filter(|record| record.outcome == outcome).count() as u32;
+
+        let mut failure_reasons: BTreeMap<FailureReason, u32> = FailureReason::ALL
+            .iter()
+            .map(|reason| (*reason, 0))
+            .collect();
+
+        for record in &records {
+            if let QueryOutcome::Failed(reason) = record.outcome {
+                *failure_reasons.entry(reason).or_default() += 1;
+            }
+        }
+
+        let answered: Vec<&&QueryRecord> = records
+            .iter()
+            .filter(|record| record.outcome == QueryOutcome::Answered)
+            .collect();
+
+        let mut latencies: Vec<u32> = answered
+            .iter()
+            .filter_map(|record| record.latency())
+            .collect();
+
+        latencies.sort_unstable();
+
+        let mut report = Self {
+            queries: records.len() as u32,
+            answered: count(QueryOutcome::Answered),
+            failed: failure_reasons.values().sum(),
+            pending: count(QueryOutcome::Pending),
+            latency_mean: mean(latencies.iter().copied()),
+            latency_p50: percentile(&latencies, 50.0),
+            latency_p90: percentile(&latencies, 90.0),
+            latency_p99: percentile(&latencies, 99.0),
+            request_hops_mean: mean(answered.iter().filter_map(|record| record.request_hops)),
+            response_hops_mean: mean(answered.iter().filter_map(|record| record.response_hops)),
+            retries_mean: mean(records.iter().map(|record| record.sequence - 1)),
+            failure_reasons,
+            ..Default::default()
+        };
+
+        if report.answered + report.failed > 0 {
+            report.delivery_ratio = report.answered as f64 / (report.answered + report.failed) as f64;
+        }
+
+        report
+    }
+}
+
+impl fmt::Display for QueryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "queries: {} (answered: {}, failed: {}, pending: {})", self.queries, self.answered, self.failed, self.pending)?;
+        writeln!(f, "delivery ratio: {:.3}", self.delivery_ratio)?;
+        writeln!(f, "latency: mean: {:.1}, p50: {}, p90: {}, p99: {}", self.latency_mean, self.latency_p50, self.latency_p90, self.latency_p99)?;
+        writeln!(f, "hops: request mean: {:.1}, response mean: {:.1}", self.request_hops_mean, self.response_hops_mean)?;
+        write!(f, "retries: mean: {:.2}", self.retries_mean)?;
+
+        if self.failed > 0 {
+            write!(f, "\nfailure reasons:")?;
+
+            for (reason, count) in &self.failure_reasons {
+                write!(f, "\n  {:<28} {:>6}", reason, count)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_id::NodeId;
+
+    fn answered(id: u32, latency: u32, sequence: u32) -> QueryRecord {
+        QueryRecord {
+            sequence,
+            completed_tick: Some(10 + latency),
+            request_hops: Some(latency),
+            response_hops: Some(latency + 1),
+            outcome: QueryOutcome::Answered,
+            ..QueryRecord::new(id, NodeId::new(0), 1, 10)
+        }
+    }
+
+    fn failed(id: u32, reason: FailureReason) -> QueryRecord {
+        QueryRecord {
+            sequence: 2,
+            completed_tick: Some(50),
+            outcome: QueryOutcome::Failed(reason),
+            ..QueryRecord::new(id, NodeId::new(0), 1, 10)
+        }
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let values: Vec<u32> = (1..=10).collect();
+
+        assert_eq!(percentile(&values, 50.0), 5);
+        assert_eq!(percentile(&values, 90.0), 9);
+        assert_eq!(percentile(&values, 99.0), 10);
+        assert_eq!(percentile(&values, 0.0), 1);
+        assert_eq!(percentile(&[7], 50.0), 7);
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn reports_answered_failed_and_pending_queries() {
+        let records = [
+            answered(1, 4, 1),
+            answered(2, 8, 2),
+            answered(3, 2, 1),
+            failed(4, FailureReason::MessageLost),
+            QueryRecord::new(5, NodeId::new(0), 1, 10),
+        ];
+        let report = QueryReport::new(records.iter());
+
+        assert_eq!((report.queries, report.answered, report.failed, report.pending), (5, 3, 1, 1));
+        assert_eq!(report.delivery_ratio, 0.75);
+        assert_eq!(report.latency_mean, 14.0 / 3.0);
+        assert_eq!((report.latency_p50, report.latency_p90, report.latency_p99), (4, 8, 8));
+        assert_eq!((report.request_hops_mean, report.response_hops_mean), (14.0 / 3.0, 17.0 / 3.0));
+        assert_eq!(report.retries_mean, 0.4);
+        assert_eq!(report.failure_reasons[&FailureReason::MessageLost], 1);
+        assert_eq!(report.failure_reasons.values().sum::<u32>(), 1);
+    }
+
+    #[test]
+    fn queries_without_outcome_have_no_delivery_ratio() {
+        let report = QueryReport::new([QueryRecord::new(1, NodeId::new(0), 1, 10)].iter());
+
+        assert_eq!(report.delivery_ratio, 0.0);
+        assert_eq!((report.latency_mean, report.latency_p50), (0.0, 0));
+        assert_eq!(report.failure_reasons.len(), FailureReason::ALL.len());
+    }
+}
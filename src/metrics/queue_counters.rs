@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::Serialize;
+use crate::messages::message::MessageKind;
+use crate::metrics::message_counters::MessageCounts;
+use crate::node_id::NodeId;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct QueueCounters {
+    mean_length: f64,
+    max_length: usize,
+    max_length_node: Option<NodeId>,
+    dropped: MessageCounts,
+    #[serde(skip)]
+    dropped_per_node: BTreeMap<NodeId, u64>,
+    #[serde(skip)]
+    length_sum: u64,
+    #[serde(skip)]
+    samples: u64,
+}
+
+impl QueueCounters {
+    pub fn record_length(&mut self, node_id: NodeId, length: usize) {
+        if length > self.max_length {
+            self.max_length = length;
+            self.max_length_node = Some(node_id);
+        }
+    }
+
+    pub fn record_drop(&mut self, node_id: NodeId, kind: MessageKind, bytes: usize) {
+        self.dropped.add(kind, bytes);
+        *self.dropped_per_node.entry(node_id).or_default() += 1;
+    }
+
+    // samples the number of queued messages across all nodes once per tick.
+    pub fn sample(&mut self, queued: usize, nodes: usize) {
+        self.length_sum += queued as u64;
+        self.samples += 1;
+        self.mean_length = self.length_sum as f64 / (self.samples * nodes.max(1) as u64) as f64;
+    }
+
+    pub fn mean_length(&self) -> f64 {
+        self.mean_length
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    pub fn dropped(&self) -> &MessageCounts {
+        &self.dropped
+    }
+
+    pub fn dropped_per_node(&self) -> &BTreeMap<NodeId, u64> {
+        &self.dropped_per_node
+    }
+}
+
+impl fmt::Display for QueueCounters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "queue length: mean: {:.2}, max: {}", self.mean_length, self.max_length)?;
+
+        if let Some(node_id) = self.max_length_node {
+            write!(f, " (node {})", node_id)?;
+        }
+
+        write!(
+            f,
+            "\ndropped: {} (agent: {}, request: {}, response: {})",
+            self.dropped.transmissions(),
+            self.dropped.agent,
+            self.dropped.request,
+            self.dropped.response
+        )?;
+
+        let most_dropped_node = self.dropped_per_node
+            .iter()
+            .max_by_key(|(_, count)| **count);
+
+        if let Some((node_id, count)) = most_dropped_node {
+            write!(f, ", max per node: {} (node {})", count, node_id)?;
+        }
+
+        Ok(())
+    }
+}
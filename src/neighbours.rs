@@ -0,0 +1,359 @@
+use std::fmt;
+use std::str::FromStr;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use crate::layout::Link;
+use crate::node_id::NodeId;
+use crate::obstacle::{Obstacle, ObstructedLink};
+use crate::position::Position;
+use crate::spatial_index::SpatialIndex;
+
+// which nodes a node can send to. Every node has a transmission range, the neighbour range
+// unless the layout gives it its own, and links may be one-way when ranges differ.
+// `QuasiUnitDisk` always links nodes within `inner` times the range and links nodes
+// further out with a probability falling linearly to zero at the range, `Nearest` links
+// every node both ways with its `k` nearest nodes regardless of range and `Asymmetric`
+// keeps only one direction of a unit disk link with the given probability.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum NeighbourModel {
+    #[default]
+    UnitDisk,
+    QuasiUnitDisk { inner: f64 },
+    Nearest { k: usize },
+    Asymmetric { probability: f64 },
+}
+
+impl NeighbourModel {
+    // whether a node with transmission range `range` reaches a node `distance` away, for a
+    // uniform draw `draw` shared by both directions of the link.
+    fn reaches(&self, distance: f64, range: f64, draw: f64) -> bool {
+        match *self {
+            NeighbourModel::QuasiUnitDisk { inner } if inner < 1.0 && distance > inner * range => {
+                draw < (range - distance) / ((1.0 - inner) * range)
+            },
+            _ => distance <= range,
+        }
+    }
+}
+
+impl FromStr for NeighbourModel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, parameter) = match value.trim().split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter.trim())),
+            None => (value.trim(), None),
+        };
+
+        let fraction = |default: Option<f64>| {
+            parameter
+                .map(|parameter| parameter.parse::<f64>().map_err(|_| format!("invalid neighbour model parameter '{}'", parameter)))
+                .transpose()?
+                .or(default)
+                .filter(|value| (0.0..=1.0).contains(value))
+                .ok_or_else(|| format!("neighbour model '{}' needs a parameter between 0 and 1", name))
+        };
+
+        match name {
+            "unit-disk" if parameter.is_none() => Ok(NeighbourModel::UnitDisk),
+            "quasi-unit-disk" => Ok(NeighbourModel::QuasiUnitDisk { inner: fraction(Some(0.5))? }),
+            "asymmetric" => Ok(NeighbourModel::Asymmetric { probability: fraction(None)? }),
+            "knn" => {
+                let k = parameter
+                    .and_then(|parameter| parameter.parse::<usize>().ok())
+                    .filter(|k| *k > 0)
+                    .ok_or_else(|| format!("neighbour model 'knn' needs a positive number of neighbours, got '{}'", parameter.unwrap_or_default()))?;
+
+                Ok(NeighbourModel::Nearest { k })
+            },
+            _ => Err(format!("expected unit-disk, quasi-unit-disk[:<inner>], knn:<k> or asymmetric:<probability>, got '{}'", value)),
+        }
+    }
+}
+
+impl TryFrom<String> for NeighbourModel {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<NeighbourModel> for String {
+    fn from(model: NeighbourModel) -> Self {
+        model.to_string()
+    }
+}
+
+impl fmt::Display for NeighbourModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NeighbourModel::UnitDisk => write!(f, "unit-disk"),
+            NeighbourModel::QuasiUnitDisk { inner } => write!(f, "quasi-unit-disk:{}", inner),
+            NeighbourModel::Nearest { k } => write!(f, "knn:{}", k),
+            NeighbourModel::Asymmetric { probability } => write!(f, "asymmetric:{}", probability),
+        }
+    }
+}
+
+fn nearest(spatial_index: &SpatialIndex, k: usize, start_range: f64) -> Vec<Vec<NodeId>> {
+    let mut neighbours = vec![Vec::new(); spatial_index.len()];
+    let k = k.min(spatial_index.len().saturating_sub(1));
+    let diagonal = spatial_index.diagonal();
+
+    for index in 0..spatial_index.len() {
+        let position = spatial_index.position(index);
+        let mut range = if start_range > 0.0 { start_range } else { 1.0 };
+        let mut candidates = spatial_index.neighbours(index, range);
+
+        // the diagonal reaches every node, so the search stops growing there.
+        while candidates.len() < k && range < diagonal {
+            range = (range * 2.0).min(diagonal);
+            candidates = spatial_index.neighbours(index, range);
+        }
+
+        candidates.sort_by(|a, b| {
+            let distance_a = position.get_distance_to(spatial_index.position(*a));
+            let distance_b = position.get_distance_to(spatial_index.position(*b));
+
+            distance_a.total_cmp(&distance_b).then(a.cmp(b))
+        });
+
+        for other in candidates.into_iter().take(k) {
+            neighbours[index].push(NodeId::new(other));
+            neighbours[other].push(NodeId::new(index));
+        }
+    }
+
+    for node_neighbours in &mut neighbours {
+        node_neighbours.sort_unstable();
+        node_neighbours.dedup();
+    }
+
+    neighbours
+}
+
+// the sorted ids every node can send to, where `ranges` holds the transmission range of every node.
+pub fn build(model: NeighbourModel, spatial_index: &SpatialIndex, ranges: &[f64], rng: &mut StdRng) -> Vec<Vec<NodeId>> {
+    if let NeighbourModel::Nearest { k } = model {
+        return nearest(spatial_index, k, ranges.iter().copied().fold(0.0, f64::max));
+    }
+
+    let mut neighbours = vec![Vec::new(); spatial_index.len()];
+    let max_range = ranges.iter().copied().fold(0.0, f64::max);
+
+    for index in 0..spatial_index.len() {
+        let position = spatial_index.position(index);
+
+        for other in spatial_index.neighbours(index, max_range) {
+            if other < index {
+                continue;
+            }
+
+            let distance = position.get_distance_to(spatial_index.position(other));
+            let draw = if let NeighbourModel::QuasiUnitDisk { .. } = model { rng.gen::<f64>() } else { 0.0 };
+
+            let mut forward = model.reaches(distance, ranges[index], draw);
+            let mut backward = model.reaches(distance, ranges[other], draw);
+
+            if let NeighbourModel::Asymmetric { probability } = model {
+                if forward && backward && rng.gen_bool(probability) {
+                    if rng.gen_bool(0.5) {
+                        forward = false;
+                    } else {
+                        backward = false;
+                    }
+                }
+            }
+
+            if forward {
+                neighbours[index].push(NodeId::new(other));
+            }
+
+            if backward {
+                neighbours[other].push(NodeId::new(index));
+            }
+        }
+    }
+
+    neighbours
+}
+
+// the sorted ids every node can send to over the links of an edge list.
+pub fn from_links(nodes: usize, links: &[Link]) -> Vec<Vec<NodeId>> {
+    let mut neighbours = vec![Vec::new(); nodes];
+
+    for link in links {
+        neighbours[link.from.index()].push(link.to);
+    }
+
+    for node_neighbours in &mut neighbours {
+        node_neighbours.sort_unstable();
+    }
+
+    neighbours
+}
+
+// removes the links the obstacles block and returns every link crossing an obstacle.
+pub fn obstruct(neighbours: &mut [Vec<NodeId>], positions: &[Position], obstacles: &[Obstacle]) -> Vec<ObstructedLink> {
+    let mut obstructed_links = Vec::new();
+
+    if obstacles.is_empty() {
+        return obstructed_links;
+    }
+
+    for (index, node_neighbours) in neighbours.iter_mut().enumerate() {
+        node_neighbours.retain(|node_id| {
+            let quality = Obstacle::quality(obstacles, &positions[index], &positions[node_id.index()]);
+
+            if quality < 1.0 {
+                obstructed_links.push(ObstructedLink { from: NodeId::new(index), to: *node_id, quality });
+            }
+
+            quality > 0.0
+        });
+    }
+
+    obstructed_links
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    fn random_positions(count: usize, rng: &mut StdRng) -> Vec<Position> {
+        (0..count).map(|_| Position::new(rng.gen_range(0.0..100.0), rng.gen_range(0.0..100.0))).collect()
+    }
+
+    fn links(neighbours: &[Vec<NodeId>]) -> Vec<(usize, usize)> {
+        neighbours
+            .iter()
+            .enumerate()
+            .flat_map(|(index, node_neighbours)| node_neighbours.iter().map(move |node_id| (index, node_id.index())))
+            .collect()
+    }
+
+    // `Node::can_reach` binary searches the neighbours, so they must be strictly ascending.
+    fn assert_sorted(neighbours: &[Vec<NodeId>]) {
+        for (index, node_neighbours) in neighbours.iter().enumerate() {
+            assert!(node_neighbours.windows(2).all(|pair| pair[0] < pair[1]), "node {}: {:?}", index, node_neighbours);
+            assert!(!node_neighbours.contains(&NodeId::new(index)), "node {} links to itself", index);
+        }
+    }
+
+    // every third node has a shorter range, giving some one-way links.
+    fn range(index: usize) -> f64 {
+        if index.is_multiple_of(3) { 8.0 } else { 12.0 }
+    }
+
+    fn build_random(model: NeighbourModel, seed: u64) -> (Vec<Position>, Vec<Vec<NodeId>>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let positions = random_positions(300, &mut rng);
+        let ranges: Vec<f64> = (0..positions.len()).map(range).collect();
+        let neighbours = build(model, &SpatialIndex::new(positions.clone(), 12.0), &ranges, &mut rng);
+
+        (positions, neighbours)
+    }
+
+    #[test]
+    fn unit_disk_links_follow_the_sender_range() {
+        let positions = vec![Position::new(0.0, 0.0), Position::new(10.0, 0.0), Position::new(30.0, 0.0)];
+        let spatial_index = SpatialIndex::new(positions, 20.0);
+        let neighbours = build(NeighbourModel::UnitDisk, &spatial_index, &[20.0, 5.0, 20.0], &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(links(&neighbours), vec![(0, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn quasi_unit_disk_keeps_inner_links_and_drops_links_beyond_the_range() {
+        let model = NeighbourModel::QuasiUnitDisk { inner: 0.5 };
+        let (positions, neighbours) = build_random(model, 1);
+        let (_, unit_disk) = build_random(NeighbourModel::UnitDisk, 1);
+
+        assert_sorted(&neighbours);
+
+        let quasi = links(&neighbours);
+        let unit = links(&unit_disk);
+
+        assert!(quasi.iter().all(|link| unit.contains(link)));
+        assert!(quasi.len() < unit.len());
+
+        for (from, to) in unit {
+            if positions[from].get_distance_to(&positions[to]) <= 0.5 * range(from) {
+                assert!(quasi.contains(&(from, to)), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn knn_links_every_node_both_ways_with_its_nearest_nodes() {
+        let (positions, neighbours) = build_random(NeighbourModel::Nearest { k: 4 }, 2);
+        let nearest = links(&neighbours);
+
+        assert_sorted(&neighbours);
+
+        for (index, position) in positions.iter().enumerate() {
+            let mut others: Vec<usize> = (0..positions.len()).filter(|other| *other != index).collect();
+
+            others.sort_by(|a, b| position.get_distance_to(&positions[*a]).total_cmp(&position.get_distance_to(&positions[*b])));
+
+            for other in &others[..4] {
+                assert!(nearest.contains(&(index, *other)) && nearest.contains(&(*other, index)), "{} <-> {}", index, other);
+            }
+        }
+
+        assert!(nearest.iter().all(|(from, to)| nearest.contains(&(*to, *from))));
+
+        let small = build(NeighbourModel::Nearest { k: 10 }, &SpatialIndex::new(positions[..3].to_vec(), 1.0), &[1.0; 3], &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(links(&small), vec![(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn asymmetric_links_keep_one_direction_of_unit_disk_links() {
+        let (_, unit_disk) = build_random(NeighbourModel::UnitDisk, 3);
+        let (_, unchanged) = build_random(NeighbourModel::Asymmetric { probability: 0.0 }, 3);
+        let (_, one_way) = build_random(NeighbourModel::Asymmetric { probability: 1.0 }, 3);
+
+        assert_sorted(&one_way);
+        assert_eq!(unchanged, unit_disk);
+
+        let unit = links(&unit_disk);
+        let one_way = links(&one_way);
+
+        for (from, to) in &unit {
+            if unit.contains(&(*to, *from)) {
+                assert!(one_way.contains(&(*from, *to)) != one_way.contains(&(*to, *from)), "{} <-> {}", from, to);
+            } else {
+                assert!(one_way.contains(&(*from, *to)), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    // both layouts used to scan every grid cell out to the far node or the long range.
+    #[test]
+    fn distant_nodes_and_long_ranges_finish() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let outlier = vec![Position::new(0.0, 0.0), Position::new(1.0, 0.0), Position::new(2.0, 0.0), Position::new(1e7, 0.0)];
+        let nearest = build(NeighbourModel::Nearest { k: 2 }, &SpatialIndex::new(outlier, 15.0), &[15.0; 4], &mut rng);
+
+        assert_eq!(nearest[3], vec![NodeId::new(1), NodeId::new(2)]);
+
+        let positions = vec![Position::new(0.0, 0.0), Position::new(1.0, 0.0), Position::new(2.0, 0.0)];
+        let unit_disk = build(NeighbourModel::UnitDisk, &SpatialIndex::new(positions, 15.0), &[1e6, 15.0, 15.0], &mut rng);
+
+        assert_eq!(links(&unit_disk), vec![(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn links_of_an_edge_list_are_sorted() {
+        let link = |from: usize, to: usize| Link { from: NodeId::new(from), to: NodeId::new(to), quality: 1.0 };
+        let neighbours = from_links(3, &[link(0, 2), link(1, 0), link(0, 1), link(2, 0)]);
+
+        assert_sorted(&neighbours);
+        assert_eq!(links(&neighbours), vec![(0, 1), (0, 2), (1, 0), (2, 0)]);
+    }
+}
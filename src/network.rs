@@ -1,48 +1,473 @@
-use std::collections::HashMap;
+use std::io;
+use crate::channel::Channel;
+use crate::latency::Latency;
+use crate::mailbox::{Mailbox, MailboxOptions};
 use crate::messages::message::Message;
+use crate::metrics::failure_reason::FailureReason;
+use crate::metrics::message_counters::MessageCounters;
+use crate::metrics::queue_counters::QueueCounters;
+use crate::node::Node;
+use crate::node_id::NodeId;
+use crate::outbox::{Action, Outbox};
+use crate::trace::{Trace, TraceNode, TraceOperation, TraceRecord};
 
 pub struct Network {
-    incoming_messages: HashMap<u32, Vec<Message>>
+    tick: u32,
+    options: MailboxOptions,
+    mailboxes: Vec<Mailbox>,
+    queued: usize,
+    channel: Option<Channel>,
+    latency: Option<Latency>,
+    dropped_queries: Vec<(u32, FailureReason)>,
+    deliveries: Option<Vec<(u32, NodeId)>>,
+    counters: MessageCounters,
+    queue_counters: QueueCounters,
+    trace: Option<Trace>,
 }
 
 impl Network {
-    pub fn send(&mut self, node_id: u32, message: Message) {
-        if !self.incoming_messages.contains_key(&node_id) {
-            self.incoming_messages.insert(node_id, Vec::new());
+    pub fn advance(&mut self, tick: u32) {
+        self.tick = tick;
+    }
+
+    pub fn send(&mut self, sender_id: NodeId, node_id: NodeId, mut message: Message) {
+        if sender_id != node_id {
+            self.counters.record(
+                self.tick,
+                sender_id,
+                message.kind(),
+                message.size()
+            );
+        }
+
+        if let Some(trace) = &mut self.trace {
+            trace.record(
+                &TraceRecord::new(
+                    self.tick,
+                    TraceOperation::Send,
+                    sender_id,
+                    node_id,
+                    &message
+                )
+            );
+        }
+
+        if sender_id != node_id && self.channel.as_mut().is_some_and(|channel| channel.is_lost(sender_id, node_id)) {
+            self.lose_message(sender_id, node_id, message);
+
+            return;
+        }
+
+        // nodes already schedule forwarded messages one tick ahead, the latency model adds the rest.
+        if sender_id != node_id {
+            if let Some(latency) = &mut self.latency {
+                message.delay(latency.delay(sender_id, node_id) - 1);
+            }
+        }
+
+        if let Some(deliveries) = &mut self.deliveries {
+            deliveries.push((message.tick(), node_id));
         }
 
-        self.incoming_messages.get_mut(&node_id).unwrap().push(message);
+        let mailbox = &mut self.mailboxes[node_id.index()];
+
+        match mailbox.push(sender_id, message, &self.options) {
+            Some((sender_id, message)) => self.drop_message(sender_id, node_id, message),
+            None => self.queued += 1,
+        }
+
+        self.queue_counters.record_length(node_id, self.mailboxes[node_id.index()].len());
     }
 
-    pub fn receive(&mut self, tick: u32, node_id: u32) -> Option<Message> {
-        let messages = self.incoming_messages.get_mut(&node_id);
+    fn lose_message(&mut self, sender_id: NodeId, node_id: NodeId, message: Message) {
+        self.counters.record_loss(message.kind(), message.size());
+        self.discard(TraceOperation::Lost, FailureReason::MessageLost, sender_id, node_id, message);
+    }
 
-        if let Some(messages) = messages {
-            let message = messages.first_mut();
+    fn drop_message(&mut self, sender_id: NodeId, node_id: NodeId, message: Message) {
+        self.queue_counters.record_drop(node_id, message.kind(), message.size());
+        self.discard(TraceOperation::Drop, FailureReason::MailboxOverflow, sender_id, node_id, message);
+    }
+
+    fn discard(&mut self, operation: TraceOperation, reason: FailureReason, sender_id: NodeId, node_id: NodeId, message: Message) {
+        match &message {
+            Message::AgentMessage(_) => {},
+            Message::RequestMessage(message) => self.dropped_queries.push((message.id, reason)),
+            Message::ResponseMessage(message) => self.dropped_queries.push((message.id, reason)),
+        }
+
+        if let Some(trace) = &mut self.trace {
+            trace.record(
+                &TraceRecord::new(
+                    self.tick,
+                    operation,
+                    sender_id,
+                    node_id,
+                    &message
+                )
+            );
+        }
+    }
+
+    // queries whose request or response was dropped from a full mailbox or lost in transit since the last call.
+    pub fn take_dropped_queries(&mut self) -> Vec<(u32, FailureReason)> {
+        std::mem::take(&mut self.dropped_queries)
+    }
 
-            if let Some(message) = message {
-                let message_tick = match message {
-                    Message::AgentMessage(message) => message.tick,
-                    Message::RequestMessage(message) => message.tick,
-                    Message::ResponseMessage(message) => message.tick,
-                };
+    // once enabled, every send is remembered as (tick due, receiver) until taken.
+    pub fn track_deliveries(&mut self) {
+        self.deliveries.get_or_insert_with(Vec::new);
+    }
+
+    pub fn take_deliveries(&mut self) -> Vec<(u32, NodeId)> {
+        self.deliveries.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn next_message_tick(&self, node_id: NodeId) -> Option<u32> {
+        self.mailboxes.get(node_id.index())?.next_tick()
+    }
+
+    pub fn sample_queues(&mut self) {
+        self.queue_counters.sample(self.queued, self.mailboxes.len());
+    }
 
-                if message_tick <= tick {
-                    Some(messages.swap_remove(0))
-                } else {
-                    None
-                }
-            } else {
-                None
+    pub fn receive(&mut self, tick: u32, node_id: NodeId) -> Option<Message> {
+        let (sender_id, message) = self.mailboxes
+            .get_mut(node_id.index())?
+            .pop(tick, &self.options.order)?;
+
+        self.queued -= 1;
+
+        if let Some(trace) = &mut self.trace {
+            trace.record(
+                &TraceRecord::new(
+                    tick,
+                    TraceOperation::Receive,
+                    sender_id,
+                    node_id,
+                    &message
+                )
+            );
+        }
+
+        Some(message)
+    }
+
+    // applies what a node did during its update: its receives are traced and its messages sent in order.
+    pub fn deliver(&mut self, outbox: &mut Outbox) {
+        self.queued -= outbox.take_received();
+
+        for action in outbox.drain_actions() {
+            match action {
+                Action::Send(sender_id, node_id, message) => self.send(sender_id, node_id, message),
+                Action::Receive(record) => {
+                    if let Some(trace) = &mut self.trace {
+                        trace.record(&*record);
+                    }
+                },
+                Action::NoNeighbour(message) => self.counters.record_no_neighbour(message.kind(), message.size()),
             }
-        } else {
-            None
         }
     }
 
-    pub fn new() -> Self {
+    pub fn mailboxes_mut(&mut self) -> &mut [Mailbox] {
+        &mut self.mailboxes
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = (NodeId, &Message)> {
+        self.mailboxes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, mailbox)| mailbox.messages().map(move |(_, message)| (NodeId::new(index), message)))
+    }
+
+    pub fn counters(&self) -> &MessageCounters {
+        &self.counters
+    }
+
+    pub fn queue_counters(&self) -> &QueueCounters {
+        &self.queue_counters
+    }
+
+    pub fn set_channel(&mut self, channel: Channel) {
+        self.channel = Some(channel);
+    }
+
+    pub fn set_latency(&mut self, latency: Latency) {
+        self.latency = Some(latency);
+    }
+
+    pub fn set_trace(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
+    pub fn trace_node(&mut self, node: &Node) {
+        if let Some(trace) = &mut self.trace {
+            trace.record(&TraceNode::new(node));
+        }
+    }
+
+    pub fn finish_trace(&mut self) -> io::Result<()> {
+        match &mut self.trace {
+            Some(trace) => trace.finish(),
+            None => Ok(())
+        }
+    }
+
+    pub fn new(nodes: usize, options: MailboxOptions) -> Self {
         Self {
-            incoming_messages: HashMap::new()
+            tick: 0,
+            options,
+            mailboxes: (0..nodes).map(|_| Mailbox::default()).collect(),
+            queued: 0,
+            channel: None,
+            latency: None,
+            dropped_queries: Vec::new(),
+            deliveries: None,
+            counters: MessageCounters::default(),
+            queue_counters: QueueCounters::default(),
+            trace: None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use super::*;
+    use crate::channel::ChannelModel;
+    use crate::event::Event;
+    use crate::latency::LatencyModel;
+    use crate::mailbox::{DropPolicy, MailboxOrder};
+    use crate::messages::agent_message::AgentMessage;
+    use crate::messages::message::MessageKind;
+    use crate::messages::request_message::RequestMessage;
+    use crate::messages::response_message::ResponseMessage;
+    use crate::position::Position;
+
+    const SENDER: NodeId = NodeId(0);
+    const RECEIVER: NodeId = NodeId(1);
+
+    fn agent(tick: u32, id: u32) -> Message {
+        Message::AgentMessage(AgentMessage::new(tick, 0, Vec::new(), id, HashMap::new()))
+    }
+
+    fn request(tick: u32, id: u32) -> Message {
+        Message::RequestMessage(RequestMessage::new(tick, 0, Vec::new(), id, 1, 0, false, 0))
+    }
+
+    fn response(tick: u32, id: u32) -> Message {
+        Message::ResponseMessage(ResponseMessage::new(tick, 0, Vec::new(), id, Vec::new(), 0, Event::new(0, 0, Position::new(0.0, 0.0)), 0))
+    }
+
+    fn network(order: MailboxOrder) -> Network {
+        Network::new(
+            2,
+            MailboxOptions {
+                order,
+                ..MailboxOptions::default()
+            }
+        )
+    }
+
+    fn bounded_network(capacity: usize, drop_policy: DropPolicy) -> Network {
+        Network::new(
+            2,
+            MailboxOptions {
+                order: MailboxOrder::Fifo,
+                capacity: Some(capacity),
+                drop_policy
+            }
+        )
+    }
+
+    fn received(network: &mut Network, tick: u32) -> Vec<(MessageKind, u32)> {
+        let mut received = Vec::new();
+
+        while let Some(message) = network.receive(tick, RECEIVER) {
+            let id = match &message {
+                Message::AgentMessage(message) => message.id,
+                Message::RequestMessage(message) => message.id,
+                Message::ResponseMessage(message) => message.id,
+            };
+
+            received.push((message.kind(), id));
         }
+
+        received
+    }
+
+    #[test]
+    fn fifo_delivers_in_arrival_order() {
+        let mut network = network(MailboxOrder::Fifo);
+
+        network.send(SENDER, RECEIVER, agent(1, 1));
+        network.send(SENDER, RECEIVER, response(1, 2));
+        network.send(SENDER, RECEIVER, request(1, 3));
+        network.send(SENDER, RECEIVER, agent(1, 4));
+
+        assert_eq!(
+            received(&mut network, 1),
+            vec![
+                (MessageKind::Agent, 1),
+                (MessageKind::Response, 2),
+                (MessageKind::Request, 3),
+                (MessageKind::Agent, 4),
+            ]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn future_messages_do_not_block_due_ones() {
+        let mut network = network(MailboxOrder::Fifo);
+
+        network.send(SENDER, RECEIVER, agent(3, 1));
+        network.send(SENDER, RECEIVER, agent(2, 2));
+        network.send(SENDER, RECEIVER, agent(1, 3));
+        network.send(SENDER, RECEIVER, agent(1, 4));
+
+        assert_eq!(received(&mut network, 1), vec![(MessageKind::Agent, 3), (MessageKind::Agent, 4)]);
+        assert_eq!(received(&mut network, 2), vec![(MessageKind::Agent, 2)]);
+        assert_eq!(received(&mut network, 3), vec![(MessageKind::Agent, 1)]);
+        assert_eq!(network.messages().count(), 0);
+    }
+
+    #[test]
+    fn late_receive_keeps_arrival_order() {
+        let mut network = network(MailboxOrder::Fifo);
+
+        network.send(SENDER, RECEIVER, agent(2, 1));
+        network.send(SENDER, RECEIVER, agent(1, 2));
+        network.send(SENDER, RECEIVER, agent(2, 3));
+
+        assert_eq!(
+            received(&mut network, 5),
+            vec![(MessageKind::Agent, 1), (MessageKind::Agent, 2), (MessageKind::Agent, 3)]
+        );
+    }
+
+    #[test]
+    fn priority_prefers_kinds_then_arrival_order() {
+        let order: MailboxOrder = "response,request,agent".parse().unwrap();
+        let mut network = network(order);
+
+        network.send(SENDER, RECEIVER, agent(1, 1));
+        network.send(SENDER, RECEIVER, request(1, 2));
+        network.send(SENDER, RECEIVER, response(2, 3));
+        network.send(SENDER, RECEIVER, agent(1, 4));
+        network.send(SENDER, RECEIVER, response(1, 5));
+        network.send(SENDER, RECEIVER, request(1, 6));
+
+        assert_eq!(
+            received(&mut network, 1),
+            vec![
+                (MessageKind::Response, 5),
+                (MessageKind::Request, 2),
+                (MessageKind::Request, 6),
+                (MessageKind::Agent, 1),
+                (MessageKind::Agent, 4),
+            ]
+        );
+        assert_eq!(received(&mut network, 2), vec![(MessageKind::Response, 3)]);
+    }
+
+    #[test]
+    fn unlisted_kinds_come_last() {
+        let order: MailboxOrder = "request".parse().unwrap();
+        let mut network = network(order);
+
+        network.send(SENDER, RECEIVER, response(1, 1));
+        network.send(SENDER, RECEIVER, agent(1, 2));
+        network.send(SENDER, RECEIVER, request(1, 3));
+
+        assert_eq!(
+            received(&mut network, 1),
+            vec![(MessageKind::Request, 3), (MessageKind::Response, 1), (MessageKind::Agent, 2)]
+        );
+    }
+
+    #[test]
+    fn drop_tail_rejects_incoming_messages() {
+        let mut network = bounded_network(2, DropPolicy::Tail);
+
+        network.send(SENDER, RECEIVER, agent(1, 1));
+        network.send(SENDER, RECEIVER, request(1, 2));
+        network.send(SENDER, RECEIVER, response(1, 3));
+
+        assert_eq!(network.take_dropped_queries(), vec![(3, FailureReason::MailboxOverflow)]);
+        assert_eq!(network.queue_counters().dropped().response, 1);
+        assert_eq!(network.queue_counters().max_length(), 2);
+        assert_eq!(received(&mut network, 1), vec![(MessageKind::Agent, 1), (MessageKind::Request, 2)]);
+    }
+
+    #[test]
+    fn drop_oldest_evicts_the_head() {
+        let mut network = bounded_network(2, DropPolicy::Oldest);
+
+        network.send(SENDER, RECEIVER, request(1, 1));
+        network.send(SENDER, RECEIVER, agent(1, 2));
+        network.send(SENDER, RECEIVER, response(1, 3));
+
+        assert_eq!(network.take_dropped_queries(), vec![(1, FailureReason::MailboxOverflow)]);
+        assert_eq!(network.queue_counters().dropped().request, 1);
+        assert_eq!(received(&mut network, 1), vec![(MessageKind::Agent, 2), (MessageKind::Response, 3)]);
+    }
+
+    #[test]
+    fn receiving_frees_capacity() {
+        let mut network = bounded_network(1, DropPolicy::Tail);
+
+        network.send(SENDER, RECEIVER, agent(1, 1));
+        assert_eq!(received(&mut network, 1), vec![(MessageKind::Agent, 1)]);
+
+        network.send(SENDER, RECEIVER, agent(1, 2));
+        assert_eq!(received(&mut network, 1), vec![(MessageKind::Agent, 2)]);
+        assert_eq!(network.queue_counters().dropped().transmissions(), 0);
+    }
+
+    #[test]
+    fn lossy_channel_loses_transmissions_but_not_local_messages() {
+        let mut network = network(MailboxOrder::Fifo);
+
+        network.set_channel(
+            Channel::new(
+                ChannelModel::Constant { loss: 1.0 },
+                vec![15.0, 15.0],
+                HashMap::new(),
+                vec![Position::new(0.0, 0.0), Position::new(10.0, 0.0)],
+                StdRng::seed_from_u64(0)
+            )
+        );
+
+        network.send(SENDER, RECEIVER, request(1, 1));
+        network.send(RECEIVER, RECEIVER, agent(1, 2));
+
+        assert_eq!(network.take_dropped_queries(), vec![(1, FailureReason::MessageLost)]);
+        assert_eq!(network.counters().lost().request, 1);
+        assert_eq!(network.counters().total().transmissions(), 1);
+        assert_eq!(received(&mut network, 1), vec![(MessageKind::Agent, 2)]);
+    }
+
+    #[test]
+    fn latency_delays_transmissions_and_reorders_them() {
+        let mut network = network(MailboxOrder::Fifo);
+
+        network.set_latency(
+            Latency::new(
+                LatencyModel::Distance { ticks_per_unit: 0.3 },
+                0,
+                vec![Position::new(0.0, 0.0), Position::new(10.0, 0.0)],
+                StdRng::seed_from_u64(0)
+            )
+        );
+
+        network.send(SENDER, RECEIVER, agent(1, 1));
+        network.send(RECEIVER, RECEIVER, agent(1, 2));
+
+        assert_eq!(received(&mut network, 1), vec![(MessageKind::Agent, 2)]);
+        assert_eq!(received(&mut network, 2), vec![]);
+        assert_eq!(received(&mut network, 3), vec![(MessageKind::Agent, 1)]);
+    }
+}
@@ -1,34 +1,41 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use rand::Rng;
+use rand::rngs::StdRng;
 use rand::prelude::SliceRandom;
 use crate::position::Position;
+use crate::node_id::NodeId;
 use crate::route::Route;
-use crate::network::Network;
+use crate::mailbox::Mailbox;
+use crate::outbox::Outbox;
 use crate::event::Event;
 use crate::messages::message::Message;
 use crate::messages::agent_message::AgentMessage;
 use crate::messages::request_message::RequestMessage;
 use crate::messages::response_message::ResponseMessage;
+use crate::metrics::query_event::QueryEvent;
+use crate::metrics::failure_reason::FailureReason;
 
 pub struct NodeOptions {
     pub agent_probability: u32,
     pub agent_max_hops: u32,
     pub request_max_hops: u32,
     pub request_retry_multiplier: u32,
+    pub service_rate: u32,
 }
 
 pub struct Node {
-    pub id: u32,
+    pub id: NodeId,
     pub position: Position,
     options: NodeOptions,
-    pub neighbour_nodes: Vec<u32>,
-    routes: HashMap<u32, Route>,
-    events: HashMap<u32, Event>,
-    request_messages: HashMap<u32, RequestMessage>
+    pub neighbour_nodes: Vec<NodeId>,
+    routes: BTreeMap<u32, Route>,
+    events: BTreeMap<u32, Event>,
+    request_messages: BTreeMap<u32, RequestMessage>,
+    rng: StdRng,
 }
 
 impl Node {
-    pub fn detect_event(&mut self, network: &mut Network, tick: u32, event: Event) {
+    pub fn detect_event(&mut self, outbox: &mut Outbox, tick: u32, event: Event) {
         self.routes.insert(
             event.id,
             Route::new(
@@ -40,14 +47,16 @@ impl Node {
 
         self.events.insert(event.id, event);
 
-        if rand::thread_rng().gen_range(0..self.options.agent_probability) == 0 {
-            network.send(
+        if self.rng.gen_range(0..self.options.agent_probability) == 0 {
+            outbox.send(
+                self.id,
                 self.id,
                 Message::AgentMessage(
                     AgentMessage::new(
                         tick,
                         0,
                         Vec::new(),
+                        self.rng.gen::<u32>(),
                         HashMap::new(),
                     )
                 )
@@ -55,15 +64,25 @@ impl Node {
         }
     }
 
-    pub fn send_request(&mut self, network: &mut Network, tick: u32, event_id: u32) {
+    pub fn send_request(&mut self, outbox: &mut Outbox, tick: u32, event_id: u32) {
         let message = RequestMessage::new(
             tick,
             0,
             Vec::new(),
-            rand::thread_rng().gen::<u32>(),
+            self.rng.gen::<u32>(),
             1,
             event_id,
-            false
+            false,
+            0
+        );
+
+        outbox.query(
+            QueryEvent::Created {
+                id: message.id,
+                node_id: self.id,
+                event_id,
+                tick
+            }
         );
 
         self.request_messages.insert(
@@ -71,34 +90,257 @@ impl Node {
             message.clone()
         );
 
-        network.send(
+        outbox.send(
+            self.id,
             self.id,
             Message::RequestMessage(message)
         );
     }
 
-    fn get_neighbour_node(&self, route: &Vec<u32>) -> u32 {
-        let possible_nodes: Vec<&u32> = self.neighbour_nodes
+    // ticks a request waits for its response before it is retried or fails.
+    fn request_timeout(&self) -> u32 {
+        self.options.request_max_hops.saturating_mul(self.options.request_retry_multiplier)
+    }
+
+    // first tick at which one of the pending requests times out.
+    pub fn next_timeout(&self) -> Option<u32> {
+        self.request_messages
+            .values()
+            .map(|message| message.tick.saturating_add(self.request_timeout()).saturating_add(1))
+            .min()
+    }
+
+    // in event id order, like `events`, so inspecting a seeded run gives the same order every time.
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.values()
+    }
+
+    pub fn route(&self, event_id: u32) -> Option<&Route> {
+        self.routes.get(&event_id)
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.events.values()
+    }
+
+    // links may be one-way, so a route or response path can lead back over a link this node cannot send on.
+    fn can_reach(&self, node_id: NodeId) -> bool {
+        node_id == self.id || self.neighbour_nodes.binary_search(&node_id).is_ok()
+    }
+
+    // `None` when the node has no neighbours at all.
+    fn get_neighbour_node(&mut self, route: &[NodeId]) -> Option<NodeId> {
+        let possible_nodes: Vec<&NodeId> = self.neighbour_nodes
             .iter()
             .filter(|id| !route.contains(id))
             .collect();
 
-        let mut rng = rand::thread_rng();
-
-        if possible_nodes.len() > 0 {
-            **possible_nodes.choose(&mut rng).unwrap()
+        if !possible_nodes.is_empty() {
+            possible_nodes.choose(&mut self.rng).map(|id| **id)
         } else {
-            *self.neighbour_nodes.choose(&mut rng).unwrap()
+            self.neighbour_nodes.choose(&mut self.rng).copied()
+        }
+    }
+
+    fn handle_message(&mut self, outbox: &mut Outbox, tick: u32, message: Message) -> u32 {
+        let mut answers_received = 0;
+
+        match message {
+            Message::AgentMessage(message) => {
+                let mut routes: HashMap<u32, Route> = HashMap::new();
+
+                for route in message.routes.values() {
+                    let local_route = self.routes.get_mut(&route.event_id);
+
+                    if let Some(local_route) = local_route {
+                        if route.shortest_distance < local_route.shortest_distance {
+                            local_route.node_id = route.node_id;
+                            local_route.shortest_distance = route.shortest_distance;
+                        }
+                    } else {
+                        self.routes.insert(
+                            route.event_id,
+                            route.clone()
+                        );
+                    }
+                }
+
+                for route in self.routes.values() {
+                    routes.insert(
+                        route.event_id,
+                        Route::new(
+                            route.event_id,
+                            self.id,
+                            route.shortest_distance + 1
+                        )
+                    );
+                }
+
+                // an agent ends once it used up its hops, and is dropped at a node without neighbours.
+                if message.hops >= self.options.agent_max_hops {
+                    return answers_received;
+                }
+
+                if let Some(receiving_node_id) = self.get_neighbour_node(&message.route) {
+                    outbox.send(
+                        self.id,
+                        receiving_node_id,
+                        Message::AgentMessage(
+                            AgentMessage::new(
+                                tick + 1,
+                                message.hops + 1,
+                                {
+                                    let mut route = message.route.clone();
+                                    route.push(self.id);
+                                    route
+                                },
+                                message.id,
+                                routes
+                            )
+                        )
+                    );
+                } else {
+                    outbox.drop_no_neighbour(Message::AgentMessage(message));
+                }
+            },
+            Message::RequestMessage(message) => {
+                let local_event = self.events.get(&message.event_id);
+
+                if let Some(local_event) = local_event {
+                    outbox.send(
+                        self.id,
+                        self.id,
+                        Message::ResponseMessage(
+                            ResponseMessage::new(
+                                tick + 1,
+                                0,
+                                Vec::new(),
+                                message.id,
+                                {
+                                    let mut route = message.route.clone();
+                                    route.reverse();
+                                    route
+                                },
+                                message.event_id,
+                                local_event.clone(),
+                                message.hops
+                            )
+                        )
+                    )
+                } else if message.is_route_found || message.hops < self.options.request_max_hops {
+                    let local_route = self.routes
+                        .get(&message.event_id)
+                        .map(|route| route.node_id)
+                        .filter(|node_id| self.can_reach(*node_id));
+
+                    let receiving_node_id = if let Some(node_id) = local_route { Some(node_id) } else { self.get_neighbour_node(&message.route) };
+
+                    let Some(receiving_node_id) = receiving_node_id else {
+                        outbox.query(QueryEvent::Dropped { id: message.id, reason: FailureReason::NoNeighbour });
+                        outbox.drop_no_neighbour(Message::RequestMessage(message));
+
+                        return answers_received;
+                    };
+
+                    outbox.send(
+                        self.id,
+                        receiving_node_id,
+                        Message::RequestMessage(
+                            RequestMessage::new(
+                                tick + 1,
+                                message.hops + 1,
+                                {
+                                    let mut route = message.route.clone();
+                                    route.push(self.id);
+                                    route
+                                },
+                                message.id,
+                                message.sequence,
+                                message.event_id,
+                                local_route.is_some(),
+                                message.route_hops + local_route.is_some() as u32
+                            )
+                        )
+                    )
+                } else {
+                    outbox.query(
+                        QueryEvent::Dropped {
+                            id: message.id,
+                            reason: if message.route_hops > 0 {
+                                FailureReason::RouteTtlExhausted
+                            } else {
+                                FailureReason::RandomWalkTtlExhausted
+                            }
+                        }
+                    );
+                }
+            },
+            Message::ResponseMessage(message) => {
+                if self.request_messages.contains_key(&message.id) {
+                    self.request_messages.remove(&message.id);
+                    
+                    answers_received += 1;
+
+                    outbox.query(
+                        QueryEvent::Answered {
+                            id: message.id,
+                            tick,
+                            request_hops: message.request_hops,
+                            response_hops: message.hops
+                        }
+                    );
+                } else {
+                    let node_id = message.path
+                        .first()
+                        .filter(|node_id| self.can_reach(**node_id));
+
+                    if let Some(node_id) = node_id {
+                        outbox.send(
+                            self.id,
+                            *node_id,
+                            Message::ResponseMessage(
+                                ResponseMessage::new(
+                                    tick + 1,
+                                    message.hops + 1,
+                                    {
+                                        let mut route = message.route.clone();
+                                        route.push(self.id);
+                                        route
+                                    },
+                                    message.id,
+                                    {
+                                        let mut path = message.path.clone();
+                                        path.remove(0);
+                                        path
+                                    },
+                                    message.event_id,
+                                    message.event,
+                                    message.request_hops
+                                )
+                            )
+                        )
+                    } else {
+                        outbox.query(
+                            QueryEvent::Dropped {
+                                id: message.id,
+                                reason: FailureReason::ResponsePathBroken
+                            }
+                        );
+                    }
+                }
+            }
         }
+
+        answers_received
     }
 
-    pub fn update(&mut self, network: &mut Network, tick: u32) -> u32 {
+    pub fn update(&mut self, mailbox: &mut Mailbox, outbox: &mut Outbox, tick: u32) -> u32 {
         {
             let mut ids_to_remove = Vec::new();
             let mut messages_resent = HashMap::new();
 
             for (id, message) in &self.request_messages {
-                if message.tick + self.options.request_max_hops * self.options.request_retry_multiplier < tick {
+                if message.tick.saturating_add(self.request_timeout()) < tick {
                     if message.sequence > 1 {
                         ids_to_remove.push(*id);
                     } else {
@@ -109,7 +351,8 @@ impl Node {
                             message.id,
                             message.sequence + 1,
                             message.event_id,
-                            false
+                            false,
+                            message.route_hops
                         );
                 
                         messages_resent.insert(
@@ -117,7 +360,8 @@ impl Node {
                             _message.clone()
                         );
                 
-                        network.send(
+                        outbox.send(
+                            self.id,
                             self.id,
                             Message::RequestMessage(_message)
                         );
@@ -127,174 +371,42 @@ impl Node {
 
             for id in ids_to_remove {
                 self.request_messages.remove(&id);
+
+                outbox.query(QueryEvent::Failed { id, tick });
             }
 
             for (id, message) in messages_resent {
+                outbox.query(QueryEvent::Retried { id, sequence: message.sequence });
+
                 *self.request_messages.get_mut(&id).unwrap() = message;
             }
         }
 
-        let message = network.receive(tick, self.id);
-
         let mut answers_received = 0;
+        let mut messages_received = 0;
 
-        if let Some(message) = message {
-            match message {
-                Message::AgentMessage(message) => {
-                    let mut routes: HashMap<u32, Route> = HashMap::new();
-
-                    for route in message.routes.values() {
-                        let local_route = self.routes.get_mut(&route.event_id);
-
-                        if let Some(local_route) = local_route {
-                            if route.shortest_distance < local_route.shortest_distance {
-                                local_route.node_id = route.node_id;
-                                local_route.shortest_distance = route.shortest_distance;
-                            }
-                        } else {
-                            self.routes.insert(
-                                route.event_id,
-                                route.clone()
-                            );
-                        }
-                    }
-
-                    for route in self.routes.values() {
-                        routes.insert(
-                            route.event_id,
-                            Route::new(
-                                route.event_id,
-                                self.id,
-                                route.shortest_distance + 1
-                            )
-                        );
-                    }
-
-                    if message.hops < self.options.agent_max_hops {
-                        let receiving_node_id = self.get_neighbour_node(&message.route);
-                        
-                        network.send(
-                            receiving_node_id,
-                            Message::AgentMessage(
-                                AgentMessage::new(
-                                    tick + 1,
-                                    message.hops + 1,
-                                    {
-                                        let mut route = message.route.clone();
-                                        route.push(self.id);
-                                        route
-                                    },
-                                    routes
-                                )
-                            )
-                        );
-                    }
-                },
-                Message::RequestMessage(message) => {
-                    let local_event = self.events.get(&message.event_id);
-
-                    if let Some(local_event) = local_event {
-                        network.send(
-                            self.id,
-                            Message::ResponseMessage(
-                                ResponseMessage::new(
-                                    tick + 1,
-                                    0,
-                                    Vec::new(),
-                                    message.id,
-                                    {
-                                        let mut route = message.route.clone();
-                                        route.reverse();
-                                        route
-                                    },
-                                    message.event_id,
-                                    local_event.clone()
-                                )
-                            )
-                        )
-                    } else if message.is_route_found || message.hops < self.options.request_max_hops {
-                        let local_route = self.routes.get(&message.event_id);
-
-                        let receiving_node_id = if let Some(local_route) = local_route { local_route.node_id } else { self.get_neighbour_node(&message.route) };
+        while self.options.service_rate == 0 || messages_received < self.options.service_rate {
+            let Some(message) = outbox.receive(mailbox, tick, self.id) else {
+                break;
+            };
 
-                        network.send(
-                            receiving_node_id, 
-                            Message::RequestMessage(
-                                RequestMessage::new(
-                                    tick + 1,
-                                    message.hops + 1,
-                                    {
-                                        let mut route = message.route.clone();
-                                        route.push(self.id);
-                                        route
-                                    },
-                                    message.id,
-                                    message.sequence,
-                                    message.event_id,
-                                    local_route.is_some()
-                                )
-                            )
-                        )
-                    }
-                },
-                Message::ResponseMessage(message) => {
-                    if self.request_messages.contains_key(&message.id) {
-                        self.request_messages.remove(&message.id);
-                        
-                        answers_received += 1;
-                        
-                        println!(
-                            "response received: event_id: {}, tick: {}, x: {}, y: {}, hops: {}", 
-                            message.event.id, 
-                            message.event.tick, 
-                            message.event.position.x, 
-                            message.event.position.y,
-                            message.hops,
-                        );
-                    } else {
-                        let node_id = message.path.get(0);
-
-                        if let Some(node_id) = node_id {
-                            network.send(
-                                *node_id,
-                                Message::ResponseMessage(
-                                    ResponseMessage::new(
-                                        tick + 1,
-                                        message.hops + 1,
-                                        {
-                                            let mut route = message.route.clone();
-                                            route.push(self.id);
-                                            route
-                                        },
-                                        message.id,
-                                        {
-                                            let mut path = message.path.clone();
-                                            path.remove(0);
-                                            path
-                                        },
-                                        message.event_id,
-                                        message.event
-                                    )
-                                )
-                            )
-                        }
-                    }
-                }
-            }
+            messages_received += 1;
+            answers_received += self.handle_message(outbox, tick, message);
         }
 
         answers_received
     }
 
-    pub fn new(id: u32, position: Position, options: NodeOptions) -> Self {
+    pub fn new(id: NodeId, position: Position, options: NodeOptions, rng: StdRng) -> Self {
         Self {
             id,
             position,
             options,
             neighbour_nodes: Vec::new(),
-            routes: HashMap::new(),
-            events: HashMap::new(),
-            request_messages: HashMap::new()
+            routes: BTreeMap::new(),
+            events: BTreeMap::new(),
+            request_messages: BTreeMap::new(),
+            rng
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+// nodes are numbered densely in layout order, so an id doubles as an index into node storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn new(index: usize) -> Self {
+        Self(index as u32)
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_round_trip_through_their_index() {
+        for index in [0, 1, 4095] {
+            assert_eq!(NodeId::new(index).index(), index);
+        }
+
+        assert!(NodeId::new(1) < NodeId::new(2));
+    }
+
+    #[test]
+    fn ids_print_and_serialize_as_plain_numbers() {
+        let id = NodeId::new(17);
+
+        assert_eq!(id.to_string(), "17");
+        assert_eq!(format!("{:>4}", id), "  17");
+        assert_eq!(serde_json::to_string(&id).unwrap(), "17");
+        assert_eq!(serde_json::from_str::<NodeId>("17").unwrap(), id);
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::node_id::NodeId;
+use crate::position::Position;
+
+// a wall from two points or a closed polygon from three or more, in the x-y plane. Every
+// link whose line of sight crosses it loses `loss` of its messages, so the default of 1
+// removes the link altogether.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Obstacle {
+    pub points: Vec<[f64; 2]>,
+    #[serde(default = "Obstacle::default_loss")]
+    pub loss: f64,
+}
+
+// a link that crosses at least one obstacle, with the share of messages it still delivers.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct ObstructedLink {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub quality: f64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ObstacleReport {
+    pub blocked: usize,
+    pub attenuated: usize,
+}
+
+impl ObstacleReport {
+    pub fn new(obstructed_links: &[ObstructedLink]) -> Self {
+        let blocked = obstructed_links.iter().filter(|link| link.quality <= 0.0).count();
+
+        Self {
+            blocked,
+            attenuated: obstructed_links.len() - blocked
+        }
+    }
+}
+
+impl fmt::Display for ObstacleReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "obstacles: {} links blocked, {} attenuated", self.blocked, self.attenuated)
+    }
+}
+
+fn orientation(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+fn on_segment(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> bool {
+    c[0] >= a[0].min(b[0]) && c[0] <= a[0].max(b[0]) && c[1] >= a[1].min(b[1]) && c[1] <= a[1].max(b[1])
+}
+
+// whether the segments a-b and c-d meet, including when they only touch.
+fn segments_intersect(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> bool {
+    let o1 = orientation(a, b, c);
+    let o2 = orientation(a, b, d);
+    let o3 = orientation(c, d, a);
+    let o4 = orientation(c, d, b);
+
+    if ((o1 > 0.0 && o2 < 0.0) || (o1 < 0.0 && o2 > 0.0)) && ((o3 > 0.0 && o4 < 0.0) || (o3 < 0.0 && o4 > 0.0)) {
+        return true;
+    }
+
+    (o1 == 0.0 && on_segment(a, b, c))
+        || (o2 == 0.0 && on_segment(a, b, d))
+        || (o3 == 0.0 && on_segment(c, d, a))
+        || (o4 == 0.0 && on_segment(c, d, b))
+}
+
+impl Obstacle {
+    fn default_loss() -> f64 {
+        1.0
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.points.len() < 2 {
+            return Err("obstacle needs at least two points".to_string());
+        }
+
+        if self.points.iter().flatten().any(|coordinate| !coordinate.is_finite()) {
+            return Err("obstacle points must be finite".to_string());
+        }
+
+        if !(0.0..=1.0).contains(&self.loss) {
+            return Err(format!("obstacle loss must be between 0 and 1, got {}", self.loss));
+        }
+
+        Ok(())
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ([f64; 2], [f64; 2])> + '_ {
+        let closing = if self.points.len() > 2 { self.points.last().zip(self.points.first()) } else { None };
+
+        self.points
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .chain(closing.map(|(last, first)| (*last, *first)))
+    }
+
+    pub fn blocks(&self, from: &Position, to: &Position) -> bool {
+        let from = [from.x, from.y];
+        let to = [to.x, to.y];
+
+        self.edges().any(|(a, b)| segments_intersect(from, to, a, b))
+    }
+
+    // the share of messages a link between the positions delivers past all obstacles.
+    pub fn quality(obstacles: &[Obstacle], from: &Position, to: &Position) -> f64 {
+        obstacles
+            .iter()
+            .filter(|obstacle| obstacle.blocks(from, to))
+            .map(|obstacle| 1.0 - obstacle.loss.clamp(0.0, 1.0))
+            .product()
+    }
+
+    pub fn new(points: Vec<[f64; 2]>, loss: f64) -> Self {
+        Self {
+            points,
+            loss
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neighbours;
+
+    #[test]
+    fn segments_intersect_when_crossing_or_touching() {
+        assert!(segments_intersect([0.0, 0.0], [10.0, 10.0], [0.0, 10.0], [10.0, 0.0]));
+        assert!(segments_intersect([0.0, 0.0], [10.0, 0.0], [5.0, 0.0], [5.0, 5.0]));
+        assert!(segments_intersect([0.0, 0.0], [10.0, 0.0], [10.0, 0.0], [20.0, 0.0]));
+        assert!(segments_intersect([0.0, 0.0], [10.0, 0.0], [5.0, 0.0], [15.0, 0.0]));
+        assert!(!segments_intersect([0.0, 0.0], [10.0, 0.0], [11.0, 0.0], [20.0, 0.0]));
+        assert!(!segments_intersect([0.0, 0.0], [10.0, 0.0], [0.0, 1.0], [10.0, 1.0]));
+        assert!(!segments_intersect([0.0, 0.0], [10.0, 10.0], [6.0, 5.0], [10.0, 0.0]));
+    }
+
+    #[test]
+    fn polygons_are_closed_and_walls_are_not() {
+        let (from, to) = (Position::new(-5.0, 5.0), Position::new(5.0, 5.0));
+        let polygon = Obstacle::new(vec![[0.0, 10.0], [0.0, 20.0], [10.0, 0.0]], 1.0);
+        let wall = Obstacle::new(vec![[0.0, 10.0], [0.0, 20.0]], 1.0);
+
+        // only the edge closing the polygon, from (10, 0) back to (0, 10), meets the link.
+        assert!(polygon.blocks(&from, &to));
+        assert!(!wall.blocks(&from, &to));
+        assert!(!polygon.blocks(&Position::new(-5.0, 5.0), &Position::new(-1.0, 5.0)));
+    }
+
+    #[test]
+    fn obstacles_block_or_attenuate_links() {
+        let positions = vec![Position::new(0.0, 0.0), Position::new(10.0, 0.0), Position::new(0.0, 10.0)];
+        let obstacles = vec![
+            Obstacle::new(vec![[5.0, -5.0], [5.0, 5.0]], 1.0),
+            Obstacle::new(vec![[-5.0, 5.0], [5.0, 5.0]], 0.5),
+            Obstacle::new(vec![[-5.0, 6.0], [5.0, 6.0]], 0.5),
+        ];
+
+        assert_eq!(Obstacle::quality(&obstacles, &positions[0], &positions[1]), 0.0);
+        assert_eq!(Obstacle::quality(&obstacles, &positions[0], &positions[2]), 0.25);
+        assert_eq!(Obstacle::quality(&obstacles, &positions[2], &positions[0]), 0.25);
+
+        let node = |index: usize| NodeId::new(index);
+        let mut links = vec![vec![node(1), node(2)], vec![node(0)], vec![node(0)]];
+        let obstructed_links = neighbours::obstruct(&mut links, &positions, &obstacles);
+        let report = ObstacleReport::new(&obstructed_links);
+
+        assert_eq!(links, vec![vec![node(2)], vec![], vec![node(0)]]);
+        assert_eq!((report.blocked, report.attenuated), (2, 2));
+        assert!(obstructed_links.contains(&ObstructedLink { from: node(2), to: node(0), quality: 0.25 }));
+    }
+}
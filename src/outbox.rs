@@ -0,0 +1,86 @@
+use crate::mailbox::{Mailbox, MailboxOrder};
+use crate::messages::message::Message;
+use crate::metrics::query_event::QueryEvent;
+use crate::node_id::NodeId;
+use crate::trace::{TraceOperation, TraceRecord};
+
+pub enum Action {
+    Send(NodeId, NodeId, Message),
+    Receive(Box<TraceRecord>),
+    NoNeighbour(Message),
+}
+
+// collects what a node does during its update so that nodes can be updated independently
+// (and in parallel) and their effects applied to the network afterwards in node order.
+pub struct Outbox {
+    order: MailboxOrder,
+    trace: bool,
+    received: usize,
+    actions: Vec<Action>,
+    query_events: Vec<QueryEvent>,
+}
+
+impl Outbox {
+    pub fn send(&mut self, sender_id: NodeId, node_id: NodeId, message: Message) {
+        self.actions.push(Action::Send(sender_id, node_id, message));
+    }
+
+    pub fn receive(&mut self, mailbox: &mut Mailbox, tick: u32, node_id: NodeId) -> Option<Message> {
+        let (sender_id, message) = mailbox.pop(tick, &self.order)?;
+
+        self.received += 1;
+
+        if self.trace {
+            self.actions.push(
+                Action::Receive(
+                    Box::new(
+                        TraceRecord::new(
+                            tick,
+                            TraceOperation::Receive,
+                            sender_id,
+                            node_id,
+                            &message
+                        )
+                    )
+                )
+            );
+        }
+
+        Some(message)
+    }
+
+    // a message the node had to forward but could not, having no neighbour at all.
+    pub fn drop_no_neighbour(&mut self, message: Message) {
+        self.actions.push(Action::NoNeighbour(message));
+    }
+
+    pub fn query(&mut self, event: QueryEvent) {
+        self.query_events.push(event);
+    }
+
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    pub fn take_received(&mut self) -> usize {
+        std::mem::take(&mut self.received)
+    }
+
+    pub fn drain_actions(&mut self) -> impl Iterator<Item = Action> + '_ {
+        self.actions.drain(..)
+    }
+
+    pub fn drain_query_events(&mut self) -> impl Iterator<Item = QueryEvent> + '_ {
+        self.query_events.drain(..)
+    }
+
+    pub fn new(order: MailboxOrder) -> Self {
+        Self {
+            order,
+            trace: false,
+            received: 0,
+            actions: Vec::new(),
+            query_events: Vec::new()
+        }
+    }
+}
@@ -1,23 +1,58 @@
-use std::cmp::{min, max};
-
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Position {
-    pub x: u32,
-    pub y: u32,
+    pub x: f64,
+    pub y: f64,
+    pub z: Option<f64>,
 }
 
 impl Position {
     pub fn get_distance_to(&self, position: &Position) -> f64 {
-        let dx = max(&self.x, &position.x) - min(&self.x, &position.x);
-        let dy = max(&self.y, &position.y) - min(&self.y, &position.y);
+        let dx = self.x - position.x;
+        let dy = self.y - position.y;
+        let dz = self.z.unwrap_or(0.0) - position.z.unwrap_or(0.0);
 
-        return (dx.pow(2) as f64 + dy.pow(2) as f64).sqrt();
+        (dx.powi(2) + dy.powi(2) + dz.powi(2)).sqrt()
     }
     
-    pub fn new(x: u32, y: u32) -> Self {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self {
+            x,
+            y,
+            z: None
+        }
+    }
+
+    pub fn new_3d(x: f64, y: f64, z: f64) -> Self {
         Self {
             x,
-            y
+            y,
+            z: Some(z)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_in_the_plane() {
+        assert_eq!(Position::new(0.0, 0.0).get_distance_to(&Position::new(3.0, 4.0)), 5.0);
+        assert_eq!(Position::new(-1.0, 2.0).get_distance_to(&Position::new(-1.0, 2.0)), 0.0);
+    }
+
+    #[test]
+    fn distances_in_space() {
+        let a = Position::new_3d(1.0, 2.0, 3.0);
+        let b = Position::new_3d(3.0, 5.0, 9.0);
+
+        assert_eq!(a.get_distance_to(&b), 7.0);
+        assert_eq!(b.get_distance_to(&a), 7.0);
+    }
+
+    #[test]
+    fn positions_without_height_sit_at_zero() {
+        assert_eq!(Position::new(0.0, 0.0).get_distance_to(&Position::new_3d(0.0, 0.0, 4.0)), 4.0);
+        assert_eq!(Position::new(0.0, 0.0).get_distance_to(&Position::new_3d(3.0, 4.0, 0.0)), 5.0);
+    }
+}
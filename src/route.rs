@@ -1,12 +1,14 @@
+use crate::node_id::NodeId;
+
 #[derive(Clone)]
 pub struct Route {
     pub event_id: u32,
-    pub node_id: u32,
+    pub node_id: NodeId,
     pub shortest_distance: u32
 }
 
 impl Route {
-    pub fn new(event_id: u32, node_id: u32, shortest_distance: u32) -> Self {
+    pub fn new(event_id: u32, node_id: NodeId, shortest_distance: u32) -> Self {
         Self {
             event_id,
             node_id,
@@ -0,0 +1,89 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap};
+use serde::{Deserialize, Serialize};
+use crate::node_id::NodeId;
+
+// `Tick` polls every node on every tick, `Event` only wakes the nodes that have a
+// message, a detection or a request timeout due. `Event` draws the gaps between detections
+// instead of drawing for every node on every tick, so the two follow different random
+// streams and agree in distribution rather than run for run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Engine {
+    #[default]
+    Tick,
+    Event,
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    detections: BinaryHeap<Reverse<(u32, NodeId)>>,
+    wakes: BTreeSet<(u32, NodeId)>,
+}
+
+impl Scheduler {
+    pub fn schedule_detection(&mut self, tick: u32, node_id: NodeId) {
+        self.detections.push(Reverse((tick, node_id)));
+    }
+
+    pub fn schedule_wake(&mut self, tick: u32, node_id: NodeId) {
+        self.wakes.insert((tick, node_id));
+    }
+
+    // both take the due entries of `tick` in node order, like a pass over all nodes would.
+    pub fn next_detection(&mut self, tick: u32) -> Option<NodeId> {
+        match self.detections.peek() {
+            Some(Reverse((next_tick, _))) if *next_tick <= tick => self.detections.pop().map(|Reverse((_, node_id))| node_id),
+            _ => None
+        }
+    }
+
+    pub fn wakes(&self) -> impl Iterator<Item = (u32, NodeId)> + '_ {
+        self.wakes.iter().copied()
+    }
+
+    pub fn next_wake(&mut self, tick: u32) -> Option<NodeId> {
+        match self.wakes.first() {
+            Some((next_tick, _)) if *next_tick <= tick => self.wakes.pop_first().map(|(_, node_id)| node_id),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every node due by `tick`, in the order they come out.
+    fn drain(tick: u32, mut next: impl FnMut(u32) -> Option<NodeId>) -> Vec<NodeId> {
+        std::iter::from_fn(|| next(tick)).collect()
+    }
+
+    #[test]
+    fn detections_come_due_in_tick_and_node_order() {
+        let mut scheduler = Scheduler::default();
+
+        for (tick, node) in [(5, 3), (2, 7), (5, 1), (9, 0), (2, 2)] {
+            scheduler.schedule_detection(tick, NodeId::new(node));
+        }
+
+        assert_eq!(drain(1, |tick| scheduler.next_detection(tick)), vec![]);
+        assert_eq!(drain(2, |tick| scheduler.next_detection(tick)), vec![NodeId::new(2), NodeId::new(7)]);
+        assert_eq!(drain(6, |tick| scheduler.next_detection(tick)), vec![NodeId::new(1), NodeId::new(3)]);
+        assert_eq!(drain(9, |tick| scheduler.next_detection(tick)), vec![NodeId::new(0)]);
+    }
+
+    #[test]
+    fn wakes_are_merged_and_come_due_in_tick_and_node_order() {
+        let mut scheduler = Scheduler::default();
+
+        for (tick, node) in [(4, 1), (3, 6), (4, 1), (3, 0), (8, 2)] {
+            scheduler.schedule_wake(tick, NodeId::new(node));
+        }
+
+        assert_eq!(drain(2, |tick| scheduler.next_wake(tick)), vec![]);
+        assert_eq!(drain(4, |tick| scheduler.next_wake(tick)), vec![NodeId::new(0), NodeId::new(6), NodeId::new(1)]);
+        assert_eq!(drain(7, |tick| scheduler.next_wake(tick)), vec![]);
+        assert_eq!(scheduler.wakes().collect::<Vec<_>>(), vec![(8, NodeId::new(2))]);
+    }
+}
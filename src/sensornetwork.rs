@@ -1,12 +1,29 @@
-use rand::Rng;
-use rand::seq::SliceRandom;
 use std::collections::HashMap;
-use crate::position::Position;
+use std::thread;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::layout::Layout;
 use crate::event::Event;
 use crate::node::{Node, NodeOptions};
+use crate::node_id::NodeId;
 use crate::network::Network;
+use crate::channel::{Channel, ChannelModel};
+use crate::latency::{Latency, LatencyModel, MAX_DELAY};
+use crate::mailbox::{DropPolicy, Mailbox, MailboxOptions, MailboxOrder};
+use crate::outbox::Outbox;
+use crate::spatial_index::SpatialIndex;
+use crate::neighbours::{self, NeighbourModel};
+use crate::obstacle::ObstructedLink;
+use crate::scheduler::{Engine, Scheduler};
+use crate::metrics::query_event::QueryEvent;
+use crate::metrics::query_metrics::QueryMetrics;
+use crate::trace::Trace;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SensorNetworkOptions {
     pub event_probability: u32,
     pub agent_probability: u32,
@@ -15,122 +32,636 @@ pub struct SensorNetworkOptions {
     pub request_max_hops: u32,
     pub request_retry_multiplier: u32,
     pub neighbour_range: f64,
+    pub neighbour_model: NeighbourModel,
+    pub mailbox_order: MailboxOrder,
+    pub mailbox_capacity: u32,
+    pub drop_policy: DropPolicy,
+    pub service_rate: u32,
+    pub engine: Engine,
+    pub update_threads: u32,
+    pub channel: ChannelModel,
+    pub latency: LatencyModel,
+    pub latency_jitter: u32,
+}
+
+impl Default for SensorNetworkOptions {
+    fn default() -> Self {
+        Self {
+            event_probability: 10000,
+            agent_probability: 2,
+            agent_max_hops: 50,
+            request_ticks: 400,
+            request_max_hops: 45,
+            request_retry_multiplier: 8,
+            neighbour_range: 15.0,
+            neighbour_model: NeighbourModel::UnitDisk,
+            mailbox_order: MailboxOrder::Fifo,
+            mailbox_capacity: 0,
+            drop_policy: DropPolicy::Tail,
+            service_rate: 1,
+            engine: Engine::Tick,
+            update_threads: 1,
+            channel: ChannelModel::Perfect,
+            latency: LatencyModel::default(),
+            latency_jitter: 0,
+        }
+    }
+}
+
+impl SensorNetworkOptions {
+    // sets an option from its command-line form, such as `15`, `fifo` or `constant:0.1`.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let name = name.replace('-', "_");
+        let value = value.trim();
+        let invalid = |error: String| format!("invalid {}: {}", name, error);
+
+        match name.as_str() {
+            "neighbour_range" => {
+                self.neighbour_range = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|range| range.is_finite() && *range >= 0.0)
+                    .ok_or_else(|| format!("{} must be a non-negative number, got '{}'", name, value))?;
+
+                return Ok(());
+            },
+            "neighbour_model" => return value.parse().map(|model| self.neighbour_model = model).map_err(invalid),
+            "mailbox_order" => return value.parse().map(|order| self.mailbox_order = order).map_err(invalid),
+            "drop_policy" => return DropPolicy::from_str(value, true).map(|policy| self.drop_policy = policy).map_err(invalid),
+            "engine" => return Engine::from_str(value, true).map(|engine| self.engine = engine).map_err(invalid),
+            "channel" => return value.parse().map(|channel| self.channel = channel).map_err(invalid),
+            "latency" => return value.parse().map(|latency| self.latency = latency).map_err(invalid),
+            _ => {}
+        }
+
+        let field = match name.as_str() {
+            "event_probability" => &mut self.event_probability,
+            "agent_probability" => &mut self.agent_probability,
+            "agent_max_hops" => &mut self.agent_max_hops,
+            "request_ticks" => &mut self.request_ticks,
+            "request_max_hops" => &mut self.request_max_hops,
+            "request_retry_multiplier" => &mut self.request_retry_multiplier,
+            "mailbox_capacity" => &mut self.mailbox_capacity,
+            "service_rate" => &mut self.service_rate,
+            "update_threads" => &mut self.update_threads,
+            "latency_jitter" => &mut self.latency_jitter,
+            _ => return Err(format!("unknown option '{}'", name))
+        };
+
+        let number = value.parse::<f64>().unwrap_or(f64::NAN);
+
+        if !(0.0..=u32::MAX as f64).contains(&number) || number.fract() != 0.0 {
+            return Err(format!("{} must be a non-negative integer, got '{}'", name, value));
+        }
+
+        *field = number as u32;
+
+        Ok(())
+    }
+
+    // rejects options the simulation cannot run with, such as a chance of one in zero.
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("event_probability", self.event_probability),
+            ("agent_probability", self.agent_probability),
+            ("request_ticks", self.request_ticks),
+        ];
+
+        for (name, value) in positive {
+            if value == 0 {
+                return Err(format!("{} must be at least 1", name));
+            }
+        }
+
+        // requests time out `request_max_hops * request_retry_multiplier` ticks after they were sent.
+        if self.request_max_hops.checked_mul(self.request_retry_multiplier).and_then(|timeout| timeout.checked_add(1)).is_none() {
+            return Err(format!(
+                "request_max_hops * request_retry_multiplier must be below {}, got {} * {}",
+                u32::MAX,
+                self.request_max_hops,
+                self.request_retry_multiplier
+            ));
+        }
+
+        if !self.neighbour_range.is_finite() || self.neighbour_range < 0.0 {
+            return Err(format!("neighbour_range must be a non-negative number, got {}", self.neighbour_range));
+        }
+
+        if self.latency_jitter > MAX_DELAY {
+            return Err(format!("latency_jitter must be at most {}, got {}", MAX_DELAY, self.latency_jitter));
+        }
+
+        Ok(())
+    }
+
+    // edge lists give the links as measured, so obstacles would have nothing to act on, and
+    // the nodes they do not place all sit at the origin, where a channel or latency that
+    // depends on the distance would see links of length zero.
+    pub fn validate_layout(&self, layout: &Layout) -> Result<(), String> {
+        if layout.links.is_some() && !layout.obstacles.is_empty() {
+            return Err("obstacles only act on links formed from positions and cannot be used with an edge list".to_string());
+        }
+
+        if layout.unplaced_nodes == 0 {
+            return Ok(());
+        }
+
+        let model = match (self.channel, self.latency) {
+            (ChannelModel::Distance { .. } | ChannelModel::Shadowing { .. }, _) => format!("channel {}", self.channel),
+            (_, LatencyModel::Distance { .. }) => format!("latency {}", self.latency),
+            _ => return Ok(()),
+        };
+
+        Err(format!(
+            "{} depends on the distance between nodes, but the edge list gives no position for {} of its {} nodes",
+            model,
+            layout.unplaced_nodes,
+            layout.positions.len()
+        ))
+    }
+}
+
+// picks the items at the given ascending indices as disjoint mutable references.
+fn select<'a, T>(items: &'a mut [T], node_ids: &[NodeId]) -> Vec<&'a mut T> {
+    let mut selected = Vec::with_capacity(node_ids.len());
+    let mut rest = items;
+    let mut offset = 0;
+
+    for node_id in node_ids {
+        let (_, tail) = std::mem::take(&mut rest).split_at_mut(node_id.index() - offset);
+        let (item, tail) = tail.split_first_mut().unwrap();
+
+        selected.push(item);
+        rest = tail;
+        offset = node_id.index() + 1;
+    }
+
+    selected
 }
 
 pub struct SensorNetwork {
     options: SensorNetworkOptions,
     network: Network,
-    nodes: HashMap<u32, Node>,
-    request_source_nodes: Vec<u32>,
+    nodes: Vec<Node>,
+    outboxes: Vec<Outbox>,
+    request_source_nodes: Vec<NodeId>,
     node_event_ids: Vec<u32>,
+    scheduler: Option<Scheduler>,
+    obstructed_links: Vec<ObstructedLink>,
     tick: u32,
+    rng: StdRng,
+    query_metrics: QueryMetrics,
 }
 
 impl SensorNetwork {
-    pub fn update(&mut self) -> u32 {
-        self.tick += 1;
+    // ticks until the next detection at a node for the event engine: each tick detects an
+    // event with a chance of one in `event_probability`, so the gaps are geometrically
+    // distributed and can be drawn at once instead of once per tick as the tick engine does.
+    fn detection_delay(&mut self) -> u32 {
+        if self.options.event_probability <= 1 {
+            return 1;
+        }
 
-        let mut rng = rand::thread_rng();
-
-        for (_, node) in &mut self.nodes {
-            if rng.gen_range(0..self.options.event_probability) == 0 {
-                let id = rng.gen::<u32>();
-                
-                self.node_event_ids.push(id);
-                
-                node.detect_event(
-                    &mut self.network,
-                    self.tick,
-                    Event::new(
-                        id,
-                        self.tick,
-                        node.position.clone()
-                    )
-                )
+        let u = 1.0 - self.rng.gen::<f64>();
+        let p = 1.0 / self.options.event_probability as f64;
+
+        ((u.ln() / (1.0 - p).ln()).floor() as u32).saturating_add(1)
+    }
+
+    // whether any node besides the one that detected the event holds a route to it.
+    fn has_spread(&self, event_id: u32) -> bool {
+        self.nodes
+            .iter()
+            .any(|node| node.route(event_id).is_some_and(|route| route.shortest_distance > 0))
+    }
+
+    // applies the query events and messages a node produced, in the order it produced them.
+    fn flush(&mut self, node_id: NodeId) {
+        let events: Vec<QueryEvent> = self.outboxes[node_id.index()].drain_query_events().collect();
+
+        for event in events {
+            // routes are never forgotten, so an event is only looked up until it has spread.
+            if let QueryEvent::Failed { id, .. } = event {
+                let event_id = self.query_metrics.record(id).map(|record| record.event_id);
+
+                if let Some(event_id) = event_id.filter(|event_id| !self.query_metrics.is_event_known(*event_id)) {
+                    if self.has_spread(event_id) {
+                        self.query_metrics.event_spread(event_id);
+                    }
+                }
+            }
+
+            self.query_metrics.record_event(event);
+        }
+
+        self.network.deliver(&mut self.outboxes[node_id.index()]);
+    }
+
+    fn detect_event(&mut self, node_id: NodeId) {
+        let id = self.rng.gen::<u32>();
+        let node = &mut self.nodes[node_id.index()];
+
+        self.node_event_ids.push(id);
+
+        node.detect_event(
+            &mut self.outboxes[node_id.index()],
+            self.tick,
+            Event::new(
+                id,
+                self.tick,
+                node.position.clone()
+            )
+        );
+
+        self.flush(node_id);
+    }
+
+    fn is_request_tick(&self) -> bool {
+        self.tick > 0 && self.tick.is_multiple_of(self.options.request_ticks) && !self.node_event_ids.is_empty()
+    }
+
+    fn send_requests(&mut self) {
+        for index in 0..self.request_source_nodes.len() {
+            let id = self.request_source_nodes[index];
+            let event_id = *self.node_event_ids
+                .choose(&mut self.rng)
+                .unwrap();
+
+            self.nodes[id.index()].send_request(
+                &mut self.outboxes[id.index()],
+                self.tick,
+                event_id
+            );
+
+            self.flush(id);
+        }
+    }
+
+    // updates the given nodes, sorted by id, against the mailboxes as they were at the start of
+    // the update phase: everything they send is only delivered once all of them are done, so
+    // the nodes can be split over threads without changing the results.
+    fn update_nodes(&mut self, node_ids: &[NodeId]) -> u32 {
+        let tick = self.tick;
+        let threads = (self.options.update_threads as usize).max(1);
+
+        let mut nodes: Vec<(&mut Node, &mut Mailbox, &mut Outbox)> = select(&mut self.nodes, node_ids)
+            .into_iter()
+            .zip(select(self.network.mailboxes_mut(), node_ids))
+            .zip(select(&mut self.outboxes, node_ids))
+            .map(|((node, mailbox), outbox)| (node, mailbox, outbox))
+            .collect();
+
+        let update = |nodes: &mut [(&mut Node, &mut Mailbox, &mut Outbox)]| -> u32 {
+            nodes
+                .iter_mut()
+                .map(|(node, mailbox, outbox)| node.update(mailbox, outbox, tick))
+                .sum()
+        };
+
+        let answers_received = if threads > 1 && nodes.len() > 1 {
+            let chunk_size = nodes.len().div_ceil(threads);
+
+            thread::scope(|scope| {
+                let handles: Vec<_> = nodes
+                    .chunks_mut(chunk_size)
+                    .map(|chunk| scope.spawn(move || update(chunk)))
+                    .collect();
+
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .sum()
+            })
+        } else {
+            update(&mut nodes)
+        };
+
+        for node_id in node_ids {
+            self.flush(*node_id);
+        }
+
+        answers_received
+    }
+
+    fn poll_nodes(&mut self) -> u32 {
+        for index in 0..self.nodes.len() {
+            if self.rng.gen_range(0..self.options.event_probability) == 0 {
+                self.detect_event(NodeId::new(index));
             }
         }
 
-        if self.tick > 0 && self.tick % self.options.request_ticks == 0 && self.node_event_ids.len() > 0 {
+        if self.is_request_tick() {
+            self.send_requests();
+        }
+
+        let node_ids: Vec<NodeId> = (0..self.nodes.len()).map(NodeId::new).collect();
+
+        self.update_nodes(&node_ids)
+    }
+
+    fn process_events(&mut self) -> u32 {
+        let mut scheduler = self.scheduler.take().unwrap_or_default();
+
+        while let Some(node_id) = scheduler.next_detection(self.tick) {
+            self.detect_event(node_id);
+
+            let next_detection = self.tick.saturating_add(self.detection_delay());
+
+            scheduler.schedule_detection(next_detection, node_id);
+        }
+
+        if self.is_request_tick() {
+            self.send_requests();
+
             for id in &self.request_source_nodes {
-                self.nodes.get_mut(id).unwrap().send_request(
-                    &mut self.network,
-                    self.tick,
-                    *self.node_event_ids
-                        .choose(&mut rng)
-                        .unwrap()
-                );
+                if let Some(timeout) = self.nodes[id.index()].next_timeout() {
+                    scheduler.schedule_wake(timeout, *id);
+                }
             }
         }
-        
-        let mut answers_received = 0;
 
-        for node in self.nodes.values_mut() {
-            answers_received += node.update(&mut self.network, self.tick);
+        for (tick, node_id) in self.network.take_deliveries() {
+            scheduler.schedule_wake(tick.max(self.tick), node_id);
+        }
+
+        let mut node_ids = Vec::new();
+
+        while let Some(node_id) = scheduler.next_wake(self.tick) {
+            node_ids.push(node_id);
+        }
+
+        node_ids.sort_unstable();
+        node_ids.dedup();
+
+        let answers_received = self.update_nodes(&node_ids);
+
+        // messages sent during the update phase are seen on the next tick at the earliest.
+        for (tick, node_id) in self.network.take_deliveries() {
+            scheduler.schedule_wake(tick.max(self.tick + 1), node_id);
+        }
+
+        for node_id in node_ids {
+            let next_ticks = [self.network.next_message_tick(node_id), self.nodes[node_id.index()].next_timeout()];
+
+            for tick in next_ticks.into_iter().flatten() {
+                scheduler.schedule_wake(tick.max(self.tick + 1), node_id);
+            }
+        }
+
+        self.scheduler = Some(scheduler);
+
+        answers_received
+    }
+
+    pub fn update(&mut self) -> u32 {
+        self.tick += 1;
+
+        self.network.advance(self.tick);
+
+        let answers_received = match self.options.engine {
+            Engine::Tick => self.poll_nodes(),
+            Engine::Event => self.process_events(),
+        };
+
+        for (id, reason) in self.network.take_dropped_queries() {
+            self.query_metrics.query_dropped(id, reason);
         }
 
+        self.network.sample_queues();
+
         answers_received
     }
 
-    pub fn new(positions: Vec<Position>, options: SensorNetworkOptions) -> Self {
-        println!("running with options: {:?}", &options);
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub fn options(&self) -> &SensorNetworkOptions {
+        &self.options
+    }
+
+    pub fn query_metrics(&self) -> &QueryMetrics {
+        &self.query_metrics
+    }
+
+    pub fn obstructed_links(&self) -> &[ObstructedLink] {
+        &self.obstructed_links
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn network_mut(&mut self) -> &mut Network {
+        &mut self.network
+    }
+
+    pub fn set_trace(&mut self, trace: Trace) {
+        self.network.set_trace(trace);
+
+        for node in &self.nodes {
+            self.network.trace_node(node);
+        }
+
+        for outbox in &mut self.outboxes {
+            outbox.set_trace(true);
+        }
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter()
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.index())
+    }
+
+    pub fn new(layout: Layout, options: SensorNetworkOptions, rng: StdRng) -> Self {
+        let Layout { positions, ranges, links, obstacles, .. } = layout;
+
+        // nodes the layout gives no range of their own use the neighbour range.
+        let ranges: Vec<f64> = (0..positions.len())
+            .map(|index| ranges.get(index).copied().flatten().unwrap_or(options.neighbour_range))
+            .collect();
+        let network = Network::new(
+            positions.len(),
+            MailboxOptions {
+                order: options.mailbox_order.clone(),
+                capacity: (options.mailbox_capacity > 0).then_some(options.mailbox_capacity as usize),
+                drop_policy: options.drop_policy,
+            }
+        );
 
         let mut sensornetwork = Self {
             options,
-            network: Network::new(),
-            nodes: HashMap::new(),
+            network,
+            nodes: Vec::with_capacity(positions.len()),
+            outboxes: Vec::with_capacity(positions.len()),
             request_source_nodes: Vec::new(),
             node_event_ids: Vec::new(),
-            tick: 0
+            scheduler: None,
+            obstructed_links: Vec::new(),
+            tick: 0,
+            rng,
+            query_metrics: QueryMetrics::default()
         };
 
-        let mut rng = rand::thread_rng();
-
-        for position in positions {
-            let id = rng.gen::<u32>();
-
-            sensornetwork.nodes.insert(
-                id,
-                Node::new(
-                    id,
-                    position,
-                    NodeOptions {
-                        agent_probability: sensornetwork.options.agent_probability,
-                        agent_max_hops: sensornetwork.options.agent_max_hops,
-                        request_max_hops: sensornetwork.options.request_max_hops,
-                        request_retry_multiplier: sensornetwork.options.request_retry_multiplier,
-                    }
-                )
+        for (index, position) in positions.iter().cloned().enumerate() {
+            let node = Node::new(
+                NodeId::new(index),
+                position,
+                NodeOptions {
+                    agent_probability: sensornetwork.options.agent_probability,
+                    agent_max_hops: sensornetwork.options.agent_max_hops,
+                    request_max_hops: sensornetwork.options.request_max_hops,
+                    request_retry_multiplier: sensornetwork.options.request_retry_multiplier,
+                    service_rate: sensornetwork.options.service_rate,
+                },
+                StdRng::seed_from_u64(sensornetwork.rng.gen())
             );
+
+            sensornetwork.nodes.push(node);
+            sensornetwork.outboxes.push(Outbox::new(sensornetwork.options.mailbox_order.clone()));
         }
 
-        let mut node_neighbours = HashMap::new();
+        // only the random neighbour models draw from the rng.
+        // obstacles only act on links formed from positions, edge lists give the links as measured.
+        let neighbour_nodes = match &links {
+            Some(links) => neighbours::from_links(positions.len(), links),
+            None => {
+                let spatial_index = SpatialIndex::new(positions.clone(), sensornetwork.options.neighbour_range);
+                let mut neighbour_nodes = neighbours::build(sensornetwork.options.neighbour_model, &spatial_index, &ranges, &mut sensornetwork.rng);
 
-        for node in sensornetwork.nodes.values() {
-            for _node in sensornetwork.nodes.values() {
-                if _node.id != node.id && node.position.get_distance_to(&_node.position) <= sensornetwork.options.neighbour_range {
-                    if !node_neighbours.contains_key(&node.id) {
-                        node_neighbours.insert(node.id, Vec::new());
-                    }
+                sensornetwork.obstructed_links = neighbours::obstruct(&mut neighbour_nodes, &positions, &obstacles);
 
-                    node_neighbours.get_mut(&node.id).unwrap().push(_node.id);
-                }
+                neighbour_nodes
             }
-        }
+        };
 
-        for (id, neighbour_nodes) in node_neighbours {
-            let node = sensornetwork.nodes.get_mut(&id).unwrap();
+        for (node, neighbour_nodes) in sensornetwork.nodes.iter_mut().zip(neighbour_nodes) {
             node.neighbour_nodes = neighbour_nodes;
         }
 
-        for _ in 0..4 {
-            sensornetwork.request_source_nodes.push(
-                *sensornetwork.nodes.keys()
-                    .skip(rand::thread_rng().gen_range(0..sensornetwork.nodes.len()))
-                    .next()
-                    .unwrap()
+        // an empty network has nobody to send queries, so it just runs idle.
+        if !sensornetwork.nodes.is_empty() {
+            for _ in 0..4 {
+                let index = sensornetwork.rng.gen_range(0..sensornetwork.nodes.len());
+
+                sensornetwork.request_source_nodes.push(NodeId::new(index));
+            }
+        }
+
+        let qualities: HashMap<(NodeId, NodeId), f64> = links
+            .iter()
+            .flatten()
+            .map(|link| ((link.from, link.to), link.quality))
+            .chain(sensornetwork.obstructed_links.iter().map(|link| ((link.from, link.to), link.quality)))
+            .filter(|(_, quality)| *quality > 0.0 && *quality < 1.0)
+            .collect();
+
+        // the default channel and latency draw nothing from the rng, keeping such runs as they were.
+        if sensornetwork.options.channel != ChannelModel::Perfect || !qualities.is_empty() {
+            sensornetwork.network.set_channel(
+                Channel::new(
+                    sensornetwork.options.channel,
+                    ranges,
+                    qualities,
+                    sensornetwork.nodes.iter().map(|node| node.position.clone()).collect(),
+                    StdRng::seed_from_u64(sensornetwork.rng.gen())
+                )
+            );
+        }
+
+        if sensornetwork.options.latency != LatencyModel::default() || sensornetwork.options.latency_jitter > 0 {
+            sensornetwork.network.set_latency(
+                Latency::new(
+                    sensornetwork.options.latency,
+                    sensornetwork.options.latency_jitter,
+                    sensornetwork.nodes.iter().map(|node| node.position.clone()).collect(),
+                    StdRng::seed_from_u64(sensornetwork.rng.gen())
+                )
             );
         }
 
+        if sensornetwork.options.engine == Engine::Event {
+            let mut scheduler = Scheduler::default();
+
+            for index in 0..sensornetwork.nodes.len() {
+                scheduler.schedule_detection(sensornetwork.detection_delay(), NodeId::new(index));
+            }
+
+            sensornetwork.scheduler = Some(scheduler);
+            sensornetwork.network.track_deliveries();
+        }
+
         sensornetwork
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    fn wakes(sensornetwork: &SensorNetwork) -> Vec<(u32, NodeId)> {
+        sensornetwork.scheduler.as_ref().map(|scheduler| scheduler.wakes().collect()).unwrap_or_default()
+    }
+
+    fn run_until(sensornetwork: &mut SensorNetwork, tick: u32) {
+        while sensornetwork.tick < tick {
+            sensornetwork.update();
+        }
+    }
+
+    #[test]
+    fn event_engine_sleeps_until_a_delivery_or_a_timeout() {
+        let (node_0, node_1, node_2) = (NodeId::new(0), NodeId::new(1), NodeId::new(2));
+        let positions = vec![Position::new(0.0, 0.0), Position::new(10.0, 0.0), Position::new(500.0, 0.0)];
+        let options = SensorNetworkOptions {
+            engine: Engine::Event,
+            event_probability: u32::MAX,
+            agent_probability: 1,
+            agent_max_hops: 1,
+            request_ticks: 20,
+            request_max_hops: 3,
+            request_retry_multiplier: 2,
+            latency: LatencyModel::Fixed { ticks: 5 },
+            ..SensorNetworkOptions::default()
+        };
+        let mut sensornetwork = SensorNetwork::new(positions.into(), options, StdRng::seed_from_u64(0));
+        let mut scheduler = Scheduler::default();
+
+        // node 0 detects an event at tick 1 and node 2, which has no neighbours, asks for it.
+        scheduler.schedule_detection(1, node_0);
+        sensornetwork.scheduler = Some(scheduler);
+        sensornetwork.request_source_nodes = vec![node_2];
+
+        // the agent leaves node 0 at tick 1 and takes five ticks to reach node 1.
+        run_until(&mut sensornetwork, 1);
+
+        assert_eq!(wakes(&sensornetwork), vec![(6, node_1)]);
+
+        run_until(&mut sensornetwork, 5);
+
+        assert_eq!(wakes(&sensornetwork), vec![(6, node_1)]);
+        assert_eq!(sensornetwork.nodes[1].routes().count(), 0);
+
+        run_until(&mut sensornetwork, 6);
+
+        assert_eq!(sensornetwork.nodes[1].routes().count(), 1);
+        assert_eq!(wakes(&sensornetwork), vec![]);
+
+        // the request cannot leave node 2, which then sleeps until it times out 3 * 2 ticks later.
+        run_until(&mut sensornetwork, 20);
+
+        assert_eq!(wakes(&sensornetwork), vec![(27, node_2)]);
+
+        run_until(&mut sensornetwork, 26);
+
+        assert!(sensornetwork.query_metrics.records().all(|record| record.sequence == 1));
+
+        run_until(&mut sensornetwork, 27);
+
+        assert!(sensornetwork.query_metrics.records().all(|record| record.sequence == 2));
+        assert_eq!(wakes(&sensornetwork), vec![(28, node_2), (34, node_2)]);
+    }
+}
@@ -0,0 +1,353 @@
+use std::io::{self, Write};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use crate::layout::Layout;
+use crate::node::Node;
+use crate::node_id::NodeId;
+use crate::route::Route;
+use crate::messages::message::Message;
+use crate::metrics::query_metrics::QueryMetrics;
+use crate::metrics::message_counters::MessageCounters;
+use crate::metrics::queue_counters::QueueCounters;
+use crate::obstacle::{ObstacleReport, ObstructedLink};
+use crate::sensornetwork::{SensorNetwork, SensorNetworkOptions};
+use crate::trace::Trace;
+
+pub struct SimulationBuilder {
+    layout: Layout,
+    options: SensorNetworkOptions,
+    seed: Option<u64>,
+    trace: Option<Trace>,
+}
+
+impl SimulationBuilder {
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn trace(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.trace = Some(Trace::new(writer));
+        self
+    }
+
+    pub fn build(self) -> Simulation {
+        let seed = self.seed.unwrap_or_else(rand::random);
+
+        let mut sensornetwork = SensorNetwork::new(
+            self.layout,
+            self.options,
+            StdRng::seed_from_u64(seed)
+        );
+
+        if let Some(trace) = self.trace {
+            sensornetwork.set_trace(trace);
+        }
+
+        Simulation {
+            seed,
+            sensornetwork,
+            answers_received: 0,
+        }
+    }
+
+    // takes a layout or just the node positions.
+    pub fn new(layout: impl Into<Layout>, options: SensorNetworkOptions) -> Self {
+        Self {
+            layout: layout.into(),
+            options,
+            seed: None,
+            trace: None
+        }
+    }
+}
+
+pub struct Simulation {
+    seed: u64,
+    sensornetwork: SensorNetwork,
+    answers_received: u32,
+}
+
+impl Simulation {
+    pub fn step(&mut self) -> u32 {
+        let answers_received = self.sensornetwork.update();
+
+        self.answers_received += answers_received;
+
+        answers_received
+    }
+
+    pub fn run(&mut self, iterations: u32) -> u32 {
+        (0..iterations).map(|_| self.step()).sum()
+    }
+
+    pub fn finish_trace(&mut self) -> io::Result<()> {
+        self.sensornetwork.network_mut().finish_trace()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.sensornetwork.tick()
+    }
+
+    pub fn options(&self) -> &SensorNetworkOptions {
+        self.sensornetwork.options()
+    }
+
+    pub fn answers_received(&self) -> u32 {
+        self.answers_received
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.sensornetwork.nodes()
+    }
+
+    // nodes with no neighbour to forward to; agents end and requests are dropped there.
+    pub fn isolated_nodes(&self) -> Vec<NodeId> {
+        self.nodes()
+            .filter(|node| node.neighbour_nodes.is_empty())
+            .map(|node| node.id)
+            .collect()
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.sensornetwork.node(id)
+    }
+
+    pub fn routes(&self, node_id: NodeId) -> impl Iterator<Item = &Route> {
+        self.node(node_id)
+            .into_iter()
+            .flat_map(|node| node.routes())
+    }
+
+    pub fn query_metrics(&self) -> &QueryMetrics {
+        self.sensornetwork.query_metrics()
+    }
+
+    pub fn message_counters(&self) -> &MessageCounters {
+        self.sensornetwork.network().counters()
+    }
+
+    // links crossing an obstacle, blocked ones included.
+    pub fn obstructed_links(&self) -> &[ObstructedLink] {
+        self.sensornetwork.obstructed_links()
+    }
+
+    pub fn obstacle_report(&self) -> ObstacleReport {
+        ObstacleReport::new(self.sensornetwork.obstructed_links())
+    }
+
+    pub fn queue_counters(&self) -> &QueueCounters {
+        self.sensornetwork.network().queue_counters()
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = (NodeId, &Message)> {
+        self.sensornetwork.network().messages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::*;
+    use crate::metrics::failure_reason::FailureReason;
+    use crate::metrics::query_record::QueryRecord;
+    use crate::position::Position;
+    use crate::scheduler::Engine;
+
+    // a trace writer the test can still read once the simulation holds it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct TracedRun {
+        records: Vec<QueryRecord>,
+        counters: MessageCounters,
+        trace: String,
+    }
+
+    fn traced_run(positions: Vec<Position>, options: SensorNetworkOptions, seed: u64, iterations: u32) -> TracedRun {
+        let buffer = SharedBuffer::default();
+        let mut simulation = SimulationBuilder::new(positions, options)
+            .seed(seed)
+            .trace(Box::new(buffer.clone()))
+            .build();
+
+        simulation.run(iterations);
+        simulation.finish_trace().unwrap();
+
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+
+        TracedRun {
+            records: simulation.query_metrics().records().cloned().collect(),
+            counters: simulation.message_counters().clone(),
+            trace,
+        }
+    }
+
+    // a grid of `side` x `side` nodes, each in range of its four neighbours.
+    fn grid(side: usize) -> Vec<Position> {
+        (0..side * side).map(|index| Position::new((index % side) as f64 * 10.0, (index / side) as f64 * 10.0)).collect()
+    }
+
+    // `count` nodes on a line, each in range of the next.
+    fn line(count: usize) -> Vec<Position> {
+        (0..count).map(|index| Position::new(index as f64 * 10.0, 0.0)).collect()
+    }
+
+    fn failure_reasons(simulation: &Simulation) -> Vec<FailureReason> {
+        simulation
+            .query_metrics()
+            .records()
+            .filter_map(|record| record.outcome.failure_reason())
+            .collect()
+    }
+
+    fn short_requests() -> SensorNetworkOptions {
+        SensorNetworkOptions {
+            event_probability: 200,
+            request_ticks: 20,
+            request_max_hops: 3,
+            request_retry_multiplier: 1,
+            service_rate: 0,
+            ..SensorNetworkOptions::default()
+        }
+    }
+
+    #[test]
+    fn a_seed_reproduces_its_run() {
+        let first = traced_run(grid(6), short_requests(), 7, 300);
+        let second = traced_run(grid(6), short_requests(), 7, 300);
+        let other = traced_run(grid(6), short_requests(), 8, 300);
+
+        assert!(!first.records.is_empty());
+        assert!(first.trace.lines().count() > 36);
+        assert_eq!(first.records, second.records);
+        assert_eq!(first.counters, second.counters);
+        assert_eq!(first.trace, second.trace);
+        assert_ne!(first.trace, other.trace);
+        assert_ne!(first.records, other.records);
+    }
+
+    #[test]
+    fn parallel_updates_match_the_sequential_run() {
+        for engine in [Engine::Tick, Engine::Event] {
+            let options = |update_threads: u32| SensorNetworkOptions {
+                engine,
+                update_threads,
+                mailbox_capacity: 2,
+                ..short_requests()
+            };
+            let sequential = traced_run(grid(8), options(1), 3, 400);
+            let parallel = traced_run(grid(8), options(4), 3, 400);
+
+            assert!(!sequential.records.is_empty(), "{:?}", engine);
+            assert_eq!(sequential.records, parallel.records, "{:?}", engine);
+            assert_eq!(sequential.counters, parallel.counters, "{:?}", engine);
+            assert_eq!(sequential.trace, parallel.trace, "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn empty_networks_run_idle() {
+        let mut simulation = SimulationBuilder::new(Vec::new(), SensorNetworkOptions::default()).seed(1).build();
+
+        assert_eq!(simulation.run(100), 0);
+        assert_eq!(simulation.query_metrics().records().count(), 0);
+    }
+
+    #[test]
+    fn queries_for_events_no_agent_spread_fail_as_unknown() {
+        let options = SensorNetworkOptions {
+            agent_probability: u32::MAX,
+            ..short_requests()
+        };
+        let mut simulation = SimulationBuilder::new(line(20), options).seed(1).build();
+
+        simulation.run(400);
+
+        let reasons = failure_reasons(&simulation);
+
+        assert!(!reasons.is_empty());
+        assert!(reasons.iter().all(|reason| *reason == FailureReason::EventUnknown));
+    }
+
+    #[test]
+    fn queries_for_events_agents_spread_are_not_unknown() {
+        let options = SensorNetworkOptions {
+            agent_probability: 1,
+            ..short_requests()
+        };
+        let mut simulation = SimulationBuilder::new(line(20), options).seed(1).build();
+
+        simulation.run(400);
+
+        let reasons = failure_reasons(&simulation);
+
+        assert!(!reasons.is_empty());
+        assert!(!reasons.contains(&FailureReason::EventUnknown));
+    }
+
+    #[test]
+    fn request_timeouts_do_not_overflow() {
+        let overflowing = SensorNetworkOptions {
+            request_max_hops: 100_000,
+            request_retry_multiplier: 100_000,
+            ..SensorNetworkOptions::default()
+        };
+
+        assert!(overflowing.validate().is_err());
+
+        // the longest valid timeout still runs past the end of time once added to the send tick.
+        let options = SensorNetworkOptions {
+            request_retry_multiplier: (u32::MAX - 1) / 3,
+            ..short_requests()
+        };
+
+        assert!(options.validate().is_ok());
+
+        let mut simulation = SimulationBuilder::new(line(5), options).seed(1).build();
+
+        simulation.run(200);
+
+        assert!(simulation.query_metrics().records().count() > 0);
+    }
+
+    #[test]
+    fn isolated_nodes_drop_agents_and_requests() {
+        // a connected pair whose agents spread its events, the other nodes far apart.
+        let positions: Vec<Position> = line(2)
+            .into_iter()
+            .chain((1..7).map(|index| Position::new(index as f64 * 100.0, 50.0)))
+            .collect();
+        let options = SensorNetworkOptions {
+            agent_probability: 1,
+            ..short_requests()
+        };
+        let mut simulation = SimulationBuilder::new(positions, options).seed(1).build();
+
+        assert_eq!(simulation.isolated_nodes().len(), 6);
+
+        simulation.run(400);
+
+        let reasons = failure_reasons(&simulation);
+        let dropped = simulation.message_counters().no_neighbour();
+
+        assert!(dropped.agent > 0);
+        assert!(dropped.request > 0);
+        assert_eq!(dropped.response, 0);
+        assert!(reasons.contains(&FailureReason::NoNeighbour));
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use crate::position::Position;
+
+type Cell = (i64, i64, i64);
+
+pub struct SpatialIndex {
+    cell_size: f64,
+    cells: HashMap<Cell, Vec<usize>>,
+    // the lowest and highest cell holding a node on every axis, so searches stay within the
+    // layout and 2D layouts only search one layer.
+    bounds: Option<(Cell, Cell)>,
+    positions: Vec<Position>,
+}
+
+impl SpatialIndex {
+    fn cell(&self, position: &Position) -> Cell {
+        if !self.cell_size.is_finite() {
+            return (0, 0, 0);
+        }
+
+        (
+            (position.x / self.cell_size).floor() as i64,
+            (position.y / self.cell_size).floor() as i64,
+            (position.z.unwrap_or(0.0) / self.cell_size).floor() as i64,
+        )
+    }
+
+    fn insert(&mut self, index: usize, cell: Cell) {
+        let (low, high) = self.bounds.unwrap_or((cell, cell));
+
+        self.bounds = Some((
+            (low.0.min(cell.0), low.1.min(cell.1), low.2.min(cell.2)),
+            (high.0.max(cell.0), high.1.max(cell.1), high.2.max(cell.2)),
+        ));
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    pub fn position(&self, index: usize) -> &Position {
+        &self.positions[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // the diagonal of the box around all nodes, a range that reaches every node from any other.
+    pub fn diagonal(&self) -> f64 {
+        let mut low = [f64::INFINITY; 3];
+        let mut high = [f64::NEG_INFINITY; 3];
+
+        for position in &self.positions {
+            for (axis, value) in [position.x, position.y, position.z.unwrap_or(0.0)].into_iter().enumerate() {
+                low[axis] = low[axis].min(value);
+                high[axis] = high[axis].max(value);
+            }
+        }
+
+        (0..3)
+            .map(|axis| (high[axis] - low[axis]).max(0.0))
+            .map(|length| length * length)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    pub fn update(&mut self, index: usize, position: Position) {
+        let old_cell = self.cell(&self.positions[index]);
+        let new_cell = self.cell(&position);
+
+        if old_cell != new_cell {
+            if let Some(indices) = self.cells.get_mut(&old_cell) {
+                indices.retain(|other| *other != index);
+
+                if indices.is_empty() {
+                    self.cells.remove(&old_cell);
+                }
+            }
+
+            self.insert(index, new_cell);
+        }
+
+        self.positions[index] = position;
+    }
+
+    pub fn within(&self, position: &Position, range: f64) -> Vec<usize> {
+        let mut indices = Vec::new();
+
+        let Some((low, high)) = self.bounds else {
+            return indices;
+        };
+
+        if range.is_nan() || range < 0.0 {
+            return indices;
+        }
+
+        let (x, y, z) = self.cell(position);
+        let reach = if self.cell_size.is_finite() { (range / self.cell_size).ceil() as i64 } else { 0 };
+
+        let from = (x.saturating_sub(reach).max(low.0), y.saturating_sub(reach).max(low.1), z.saturating_sub(reach).max(low.2));
+        let to = (x.saturating_add(reach).min(high.0), y.saturating_add(reach).min(high.1), z.saturating_add(reach).min(high.2));
+
+        let mut push = |cell_indices: &Vec<usize>| {
+            for index in cell_indices {
+                if position.get_distance_to(&self.positions[*index]) <= range {
+                    indices.push(*index);
+                }
+            }
+        };
+
+        let searched_cells = [(from.0, to.0), (from.1, to.1), (from.2, to.2)]
+            .into_iter()
+            .map(|(from, to)| (to as f64 - from as f64 + 1.0).max(0.0))
+            .product::<f64>();
+
+        // a range far beyond the cell size would scan mostly empty cells, so visit the
+        // occupied ones instead once there are fewer of them.
+        if searched_cells > self.cells.len() as f64 {
+            for (cell, cell_indices) in &self.cells {
+                if (from.0..=to.0).contains(&cell.0) && (from.1..=to.1).contains(&cell.1) && (from.2..=to.2).contains(&cell.2) {
+                    push(cell_indices);
+                }
+            }
+        } else {
+            for cx in from.0..=to.0 {
+                for cy in from.1..=to.1 {
+                    for cz in from.2..=to.2 {
+                        if let Some(cell_indices) = self.cells.get(&(cx, cy, cz)) {
+                            push(cell_indices);
+                        }
+                    }
+                }
+            }
+        }
+
+        indices.sort_unstable();
+
+        indices
+    }
+
+    pub fn neighbours(&self, index: usize, range: f64) -> Vec<usize> {
+        let mut indices = self.within(&self.positions[index], range);
+
+        indices.retain(|other| *other != index);
+
+        indices
+    }
+
+    pub fn new(positions: Vec<Position>, cell_size: f64) -> Self {
+        let mut index = Self {
+            cell_size: if cell_size > 0.0 { cell_size } else { f64::INFINITY },
+            cells: HashMap::new(),
+            bounds: None,
+            positions: Vec::new(),
+        };
+
+        for (i, position) in positions.iter().enumerate() {
+            let cell = index.cell(position);
+
+            index.insert(i, cell);
+        }
+
+        index.positions = positions;
+
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use super::*;
+
+    fn random_positions(count: usize, depth: f64, rng: &mut StdRng) -> Vec<Position> {
+        (0..count)
+            .map(|_| {
+                let (x, y) = (rng.gen_range(-50.0..150.0), rng.gen_range(0.0..100.0));
+
+                if depth > 0.0 { Position::new_3d(x, y, rng.gen_range(0.0..depth)) } else { Position::new(x, y) }
+            })
+            .collect()
+    }
+
+    // the O(n²) comparison of every pair the index replaces.
+    fn brute_force(positions: &[Position], index: usize, range: f64) -> Vec<usize> {
+        (0..positions.len())
+            .filter(|other| *other != index && positions[index].get_distance_to(&positions[*other]) <= range)
+            .collect()
+    }
+
+    #[test]
+    fn matches_the_brute_force_neighbours() {
+        let mut rng = StdRng::seed_from_u64(11);
+
+        for depth in [0.0, 40.0] {
+            let positions = random_positions(300, depth, &mut rng);
+            let spatial_index = SpatialIndex::new(positions.clone(), 15.0);
+
+            for range in [0.0, 7.5, 15.0, 40.0] {
+                for index in 0..positions.len() {
+                    assert_eq!(spatial_index.neighbours(index, range), brute_force(&positions, index, range));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn matches_the_brute_force_neighbours_after_updates() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let mut positions = random_positions(200, 0.0, &mut rng);
+        let mut spatial_index = SpatialIndex::new(positions.clone(), 10.0);
+
+        for (index, position) in random_positions(50, 30.0, &mut rng).into_iter().enumerate() {
+            positions[index * 4] = position.clone();
+            spatial_index.update(index * 4, position);
+        }
+
+        for index in 0..positions.len() {
+            assert_eq!(spatial_index.neighbours(index, 12.0), brute_force(&positions, index, 12.0));
+        }
+    }
+
+    #[test]
+    fn searches_a_single_layer_for_2d_layouts() {
+        let spatial_index = SpatialIndex::new(vec![Position::new(0.0, 0.0), Position::new(3.0, 4.0)], 1.0);
+
+        assert_eq!(spatial_index.bounds, Some(((0, 0, 0), (3, 4, 0))));
+        assert_eq!(spatial_index.within(&Position::new_3d(0.0, 0.0, 5.0), 5.5), vec![0]);
+    }
+
+    #[test]
+    fn huge_ranges_only_visit_occupied_cells() {
+        let positions = vec![Position::new(0.0, 0.0), Position::new(1.0, 0.0), Position::new(1e7, 0.0)];
+        let spatial_index = SpatialIndex::new(positions.clone(), 0.5);
+
+        assert_eq!(spatial_index.neighbours(0, 1e12), vec![1, 2]);
+        assert_eq!(spatial_index.neighbours(2, f64::INFINITY), vec![0, 1]);
+        assert_eq!(spatial_index.neighbours(0, 5e6), brute_force(&positions, 0, 5e6));
+        assert_eq!(spatial_index.diagonal(), 1e7);
+    }
+}
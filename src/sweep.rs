@@ -0,0 +1,401 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use crate::layout::Layout;
+use crate::sensornetwork::SensorNetworkOptions;
+use crate::simulation::SimulationBuilder;
+
+// options whose values are names or models, which may themselves contain `,` and `:`.
+const TEXT_OPTIONS: [&str; 6] = ["neighbour_model", "mailbox_order", "drop_policy", "engine", "channel", "latency"];
+
+#[derive(Clone, Debug)]
+pub struct SweepParameter {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl FromStr for SweepParameter {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, values) = value
+            .split_once('=')
+            .ok_or_else(|| format!("expected <name>=<values>, got '{}'", value))?;
+
+        let name = name.trim().replace('-', "_");
+
+        let parse = |value: &str| value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid value '{}' for {}", value, name));
+
+        let values: Vec<String> = if TEXT_OPTIONS.contains(&name.as_str()) {
+            values
+                .split(';')
+                .map(|value| value.trim().to_string())
+                .collect()
+        } else if let Some((start, rest)) = values.split_once(':') {
+            let (end, step) = rest.split_once(':').unwrap_or((rest, "1"));
+            let (start, end, step) = (parse(start)?, parse(end)?, parse(step)?);
+
+            if step <= 0.0 {
+                return Err(format!("step for {} must be positive", name));
+            }
+
+            (0..)
+                .map(|index| start + index as f64 * step)
+                .take_while(|value| *value <= end + step * 1e-9)
+                .map(|value| value.to_string())
+                .collect()
+        } else {
+            values
+                .split(',')
+                .map(|value| value.trim().to_string())
+                .collect()
+        };
+
+        if values.is_empty() {
+            return Err(format!("no values for {}", name));
+        }
+
+        for value in &values {
+            SensorNetworkOptions::default().set(&name, value)?;
+        }
+
+        Ok(Self {
+            name,
+            values
+        })
+    }
+}
+
+// quotes values such as mailbox orders that contain the separator.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Statistic {
+    pub mean: f64,
+    pub ci95: f64,
+}
+
+// two-sided 95% critical values of Student's t distribution for 1 to 30 degrees of freedom.
+const T_CRITICAL_VALUES: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+impl Statistic {
+    pub fn new(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+
+        let count = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / count;
+
+        if samples.len() < 2 {
+            return Self {
+                mean,
+                ci95: 0.0
+            };
+        }
+
+        let variance = samples.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / (count - 1.0);
+        let t = T_CRITICAL_VALUES.get(samples.len() - 2).copied().unwrap_or(1.96);
+
+        Self {
+            mean,
+            ci95: t * (variance / count).sqrt()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReplicateResult {
+    // `None` when no query of the replicate was answered or failed.
+    pub delivery_ratio: Option<f64>,
+    // `None` when the replicate answered no query.
+    pub latency: Option<f64>,
+    pub transmissions: f64,
+    pub bytes: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct SweepPoint {
+    pub values: Vec<String>,
+    pub replicates: usize,
+    // over the replicates that completed at least one query, `None` when none did.
+    pub delivery_ratio: Option<Statistic>,
+    // over the replicates that answered at least one query, `None` when none did.
+    pub latency: Option<Statistic>,
+    pub transmissions: Statistic,
+    pub bytes: Statistic,
+}
+
+impl SweepPoint {
+    fn new(values: Vec<String>, results: &[ReplicateResult]) -> Self {
+        let statistic = |sample: fn(&ReplicateResult) -> f64| {
+            Statistic::new(&results.iter().map(sample).collect::<Vec<f64>>())
+        };
+
+        // replicates without data for a measure are left out of its statistic.
+        let optional_statistic = |sample: fn(&ReplicateResult) -> Option<f64>| {
+            let samples: Vec<f64> = results.iter().filter_map(sample).collect();
+
+            (!samples.is_empty()).then(|| Statistic::new(&samples))
+        };
+
+        Self {
+            values,
+            replicates: results.len(),
+            delivery_ratio: optional_statistic(|result| result.delivery_ratio),
+            latency: optional_statistic(|result| result.latency),
+            transmissions: statistic(|result| result.transmissions),
+            bytes: statistic(|result| result.bytes),
+        }
+    }
+}
+
+pub struct Sweep {
+    pub layout: Layout,
+    pub options: SensorNetworkOptions,
+    pub parameters: Vec<SweepParameter>,
+    pub replicates: usize,
+    pub iterations: u32,
+    pub seed: u64,
+    pub threads: usize,
+}
+
+impl Sweep {
+    pub fn points(&self) -> Vec<Vec<String>> {
+        self.parameters.iter().fold(vec![Vec::new()], |points, parameter| {
+            points
+                .iter()
+                .flat_map(|point| parameter.values.iter().map(move |value| {
+                    let mut point = point.clone();
+                    point.push(value.clone());
+                    point
+                }))
+                .collect()
+        })
+    }
+
+    fn options(&self, point: &[String]) -> Result<SensorNetworkOptions, String> {
+        let mut options = self.options.clone();
+
+        for (parameter, value) in self.parameters.iter().zip(point) {
+            options.set(&parameter.name, value)?;
+        }
+
+        options.validate()?;
+        options.validate_layout(&self.layout)?;
+
+        Ok(options)
+    }
+
+    fn run_replicate(&self, options: SensorNetworkOptions, replicate: usize) -> ReplicateResult {
+        let mut simulation = SimulationBuilder::new(self.layout.clone(), options)
+            .seed(self.seed.wrapping_add(replicate as u64))
+            .build();
+
+        simulation.run(self.iterations);
+
+        let report = simulation.query_metrics().report();
+        let counts = simulation.message_counters().total();
+
+        ReplicateResult {
+            delivery_ratio: (report.answered + report.failed > 0).then_some(report.delivery_ratio),
+            latency: (report.answered > 0).then_some(report.latency_mean),
+            transmissions: counts.transmissions() as f64,
+            bytes: counts.bytes as f64,
+        }
+    }
+
+    // checks the options of every point before starting any replicate, so a bad value
+    // fails the sweep up front instead of a worker thread halfway through.
+    pub fn run(&self) -> Result<Vec<SweepPoint>, String> {
+        let points = self.points();
+
+        let options = points
+            .iter()
+            .map(|point| {
+                self.options(point).map_err(|error| {
+                    let values: Vec<String> = self.parameters
+                        .iter()
+                        .zip(point)
+                        .map(|(parameter, value)| format!("{}={}", parameter.name, value))
+                        .collect();
+
+                    format!("{}: {}", values.join(" "), error)
+                })
+            })
+            .collect::<Result<Vec<SensorNetworkOptions>, String>>()?;
+
+        let jobs = points.len() * self.replicates;
+        let next_job = AtomicUsize::new(0);
+        let results = Mutex::new(vec![ReplicateResult::default(); jobs]);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.clamp(1, jobs.max(1)) {
+                scope.spawn(|| loop {
+                    let job = next_job.fetch_add(1, Ordering::Relaxed);
+
+                    if job >= jobs {
+                        break;
+                    }
+
+                    let result = self.run_replicate(options[job / self.replicates].clone(), job % self.replicates);
+
+                    results.lock().unwrap()[job] = result;
+                });
+            }
+        });
+
+        let results = results.into_inner().unwrap();
+
+        Ok(
+            points
+                .into_iter()
+                .zip(results.chunks(self.replicates.max(1)))
+                .map(|(values, results)| SweepPoint::new(values, results))
+                .collect()
+        )
+    }
+
+    pub fn write_csv<W: Write>(&self, points: &[SweepPoint], mut writer: W) -> io::Result<()> {
+        for parameter in &self.parameters {
+            write!(writer, "{},", parameter.name)?;
+        }
+
+        writeln!(
+            writer,
+            "replicates,delivery_ratio_mean,delivery_ratio_ci95,latency_mean,latency_ci95,transmissions_mean,transmissions_ci95,bytes_mean,bytes_ci95"
+        )?;
+
+        for point in points {
+            writeln!(writer, "{}", point)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for SweepPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for value in &self.values {
+            write!(f, "{},", csv_field(value))?;
+        }
+
+        write!(f, "{},", self.replicates)?;
+
+        match self.delivery_ratio {
+            Some(delivery_ratio) => write!(f, "{:.4},{:.4},", delivery_ratio.mean, delivery_ratio.ci95)?,
+            None => write!(f, ",,")?,
+        }
+
+        match self.latency {
+            Some(latency) => write!(f, "{:.2},{:.2},", latency.mean, latency.ci95)?,
+            None => write!(f, ",,")?,
+        }
+
+        write!(
+            f,
+            "{:.1},{:.1},{:.1},{:.1}",
+            self.transmissions.mean,
+            self.transmissions.ci95,
+            self.bytes.mean,
+            self.bytes.ci95
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    fn sweep(parameters: &[&str]) -> Sweep {
+        Sweep {
+            layout: vec![Position::new(0.0, 0.0), Position::new(10.0, 0.0)].into(),
+            options: SensorNetworkOptions::default(),
+            parameters: parameters.iter().map(|parameter| parameter.parse().unwrap()).collect(),
+            replicates: 2,
+            iterations: 10,
+            seed: 0,
+            threads: 2,
+        }
+    }
+
+    #[test]
+    fn parses_ranges_lists_and_text_values() {
+        let range: SweepParameter = "agent-max-hops=20:60:20".parse().unwrap();
+        let orders: SweepParameter = "mailbox_order=fifo;response,request".parse().unwrap();
+        let channels: SweepParameter = "channel=perfect;constant:0.1".parse().unwrap();
+
+        assert_eq!((range.name.as_str(), range.values), ("agent_max_hops", vec!["20".to_string(), "40".to_string(), "60".to_string()]));
+        assert_eq!(orders.values, vec!["fifo", "response,request"]);
+        assert_eq!(channels.values, vec!["perfect", "constant:0.1"]);
+        assert!("engine=tick;warp".parse::<SweepParameter>().is_err());
+        assert!("agent_max_hops=1.5".parse::<SweepParameter>().is_err());
+    }
+
+    #[test]
+    fn invalid_points_fail_before_any_replicate_runs() {
+        let error = sweep(&["agent_probability=2,0"]).run().unwrap_err();
+
+        assert!(error.starts_with("agent_probability=0:"), "{}", error);
+
+        let error = sweep(&["request_max_hops=100000", "request_retry_multiplier=100000"]).run().unwrap_err();
+
+        assert!(error.starts_with("request_max_hops=100000 request_retry_multiplier=100000:"), "{}", error);
+    }
+
+    #[test]
+    fn edge_lists_reject_distance_models_with_unplaced_nodes_and_obstacles() {
+        let mut sweep = sweep(&["channel=constant:0.1;distance"]);
+
+        sweep.layout = crate::layout::parse_edge_list("3\nnode 0 0,0\nnode 1 10,0\nedge 0 1\nedge 1 2\n").unwrap();
+
+        assert_eq!(sweep.layout.unplaced_nodes, 1);
+        assert!(sweep.run().unwrap_err().starts_with("channel=distance: channel distance:2 depends on the distance"));
+
+        sweep.parameters = vec!["latency=fixed:2;distance:0.5".parse().unwrap()];
+
+        assert!(sweep.run().unwrap_err().starts_with("latency=distance:0.5:"));
+
+        sweep.parameters = Vec::new();
+        sweep.layout.obstacles.push(crate::obstacle::Obstacle::new(vec![[0.0, 5.0], [10.0, 5.0]], 1.0));
+
+        assert!(sweep.run().unwrap_err().contains("obstacles only act on links formed from positions"));
+    }
+
+    #[test]
+    fn replicates_without_completed_queries_have_no_delivery_ratio_or_latency() {
+        let points = sweep(&["engine=tick;event"]).run().unwrap();
+
+        assert_eq!(points.len(), 2);
+        assert!(points.iter().all(|point| point.replicates == 2 && point.delivery_ratio.is_none() && point.latency.is_none()));
+        assert_eq!(points[0].to_string(), "tick,2,,,,,0.0,0.0,0.0,0.0");
+
+        let results = [
+            ReplicateResult { delivery_ratio: Some(0.5), latency: Some(10.0), ..ReplicateResult::default() },
+            ReplicateResult::default(),
+            ReplicateResult { delivery_ratio: Some(1.0), latency: None, ..ReplicateResult::default() },
+        ];
+        let point = SweepPoint::new(Vec::new(), &results);
+
+        assert_eq!(point.delivery_ratio.map(|statistic| statistic.mean), Some(0.75));
+        assert_eq!(point.latency.map(|statistic| statistic.mean), Some(10.0));
+        assert_eq!(csv_field("response,request"), "\"response,request\"");
+    }
+}
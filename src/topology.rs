@@ -0,0 +1,329 @@
+use std::collections::HashSet;
+use std::f64::consts::PI;
+use rand::Rng;
+use rand::rngs::StdRng;
+use crate::position::Position;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Topology {
+    Grid,
+    HexGrid,
+    Uniform,
+    PoissonDisk,
+    Clustered { clusters: usize, spread: f64 },
+    PerturbedGrid { jitter: f64 },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Area {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Area {
+    fn clamp(&self, x: f64, y: f64) -> (f64, f64) {
+        (x.clamp(0.0, self.width), y.clamp(0.0, self.height))
+    }
+}
+
+struct Placement {
+    area: Area,
+    positions: Vec<Position>,
+    occupied: HashSet<(u64, u64)>,
+}
+
+impl Placement {
+    fn try_place(&mut self, x: f64, y: f64) -> bool {
+        let (x, y) = self.area.clamp(x, y);
+
+        if !self.occupied.insert(((x + 0.0).to_bits(), (y + 0.0).to_bits())) {
+            return false;
+        }
+
+        self.positions.push(Position::new(x, y));
+
+        true
+    }
+
+    fn new(area: Area, nodes: usize) -> Self {
+        Self {
+            area,
+            positions: Vec::with_capacity(nodes),
+            occupied: HashSet::with_capacity(nodes),
+        }
+    }
+}
+
+fn grid_shape(area: Area, nodes: usize) -> (usize, usize) {
+    let aspect = area.width.max(1.0) / area.height.max(1.0);
+    let columns = ((nodes as f64 * aspect).sqrt().ceil() as usize).clamp(1, nodes.max(1));
+    let rows = nodes.div_ceil(columns).max(1);
+
+    (columns, rows)
+}
+
+fn spacing(length: f64, count: usize) -> f64 {
+    if count > 1 {
+        length / (count - 1) as f64
+    } else {
+        0.0
+    }
+}
+
+fn grid(area: Area, nodes: usize) -> Vec<(f64, f64)> {
+    let (columns, rows) = grid_shape(area, nodes);
+    let (dx, dy) = (spacing(area.width, columns), spacing(area.height, rows));
+
+    (0..nodes)
+        .map(|index| ((index % columns) as f64 * dx, (index / columns) as f64 * dy))
+        .collect()
+}
+
+fn hex_grid(area: Area, nodes: usize) -> Vec<(f64, f64)> {
+    let row_height = 3f64.sqrt() / 2.0;
+    let mut side = (area.width.max(1.0) * area.height.max(1.0) / (nodes.max(1) as f64 * row_height)).sqrt();
+
+    loop {
+        let columns = (area.width / side).floor() as usize + 1;
+        let rows = (area.height / (side * row_height)).floor() as usize + 1;
+
+        // odd rows are shifted by half a cell and lose their last column if it would leave the area.
+        let odd_columns = if (columns - 1) as f64 * side + side / 2.0 <= area.width { columns } else { columns - 1 };
+
+        if rows.div_ceil(2) * columns + rows / 2 * odd_columns >= nodes || side < 1e-3 {
+            let mut points = Vec::with_capacity(nodes);
+
+            for row in 0..rows {
+                let (offset, count) = if row % 2 == 0 { (0.0, columns) } else { (side / 2.0, odd_columns) };
+
+                for column in 0..count {
+                    points.push((offset + column as f64 * side, row as f64 * side * row_height));
+                }
+            }
+
+            points.truncate(nodes);
+
+            return points;
+        }
+
+        side *= 0.99;
+    }
+}
+
+fn gaussian(rng: &mut StdRng) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
+
+fn poisson_disk(placement: &mut Placement, nodes: usize, rng: &mut StdRng) {
+    let area = placement.area;
+    let mut radius = (area.width * area.height / nodes.max(1) as f64).sqrt() * 0.75;
+    let mut points: Vec<(f64, f64)> = Vec::with_capacity(nodes);
+
+    while placement.positions.len() < nodes {
+        let cell = (radius / 2f64.sqrt()).max(1e-3);
+        let columns = (area.width / cell) as usize + 1;
+        let rows = (area.height / cell) as usize + 1;
+        let mut cells: Vec<Option<usize>> = vec![None; columns * rows];
+
+        for (index, (x, y)) in points.iter().enumerate() {
+            cells[(y / cell) as usize * columns + (x / cell) as usize] = Some(index);
+        }
+
+        let mut attempts = 0;
+
+        while placement.positions.len() < nodes && attempts < 30 * nodes {
+            attempts += 1;
+
+            let (x, y) = (rng.gen_range(0.0..=area.width), rng.gen_range(0.0..=area.height));
+            let (column, row) = ((x / cell) as usize, (y / cell) as usize);
+
+            let is_free = (row.saturating_sub(2)..(row + 3).min(rows))
+                .flat_map(|row| (column.saturating_sub(2)..(column + 3).min(columns)).map(move |column| row * columns + column))
+                .filter_map(|index| cells[index])
+                .all(|index| {
+                    let (px, py) = points[index];
+                    (px - x).powi(2) + (py - y).powi(2) >= radius * radius
+                });
+
+            if is_free && placement.try_place(x, y) {
+                cells[row * columns + column] = Some(points.len());
+                points.push((x, y));
+            }
+        }
+
+        radius *= 0.9;
+    }
+}
+
+pub fn generate(topology: Topology, area: Area, nodes: usize, rng: &mut StdRng) -> Result<Vec<Position>, String> {
+    if !(area.width >= 0.0 && area.height >= 0.0 && area.width.is_finite() && area.height.is_finite()) {
+        return Err(format!("invalid area {}x{}", area.width, area.height));
+    }
+
+    if nodes == 0 {
+        return Err("a layout needs at least one node".to_string());
+    }
+
+    let scatter = match topology {
+        Topology::Clustered { spread, .. } => Some(("spread", spread)),
+        Topology::PerturbedGrid { jitter } => Some(("jitter", jitter)),
+        _ => None,
+    };
+
+    if let Some((name, value)) = scatter.filter(|(_, value)| !(value.is_finite() && *value >= 0.0)) {
+        return Err(format!("{} must be a non-negative number, got {}", name, value));
+    }
+
+    if nodes > 1 && area.width == 0.0 && area.height == 0.0 {
+        return Err(format!("an area of {}x{} cannot hold {} distinct nodes", area.width, area.height, nodes));
+    }
+
+    let mut placement = Placement::new(area, nodes);
+
+    match topology {
+        Topology::Grid | Topology::HexGrid => {
+            let points = match topology {
+                Topology::Grid => grid(area, nodes),
+                _ => hex_grid(area, nodes),
+            };
+
+            for (x, y) in points {
+                if !placement.try_place(x, y) {
+                    return Err(format!("an area of {}x{} is too small for a {:?} of {} nodes", area.width, area.height, topology, nodes));
+                }
+            }
+        },
+        Topology::Uniform => {
+            while placement.positions.len() < nodes {
+                placement.try_place(
+                    rng.gen_range(0.0..=area.width),
+                    rng.gen_range(0.0..=area.height)
+                );
+            }
+        },
+        Topology::PoissonDisk => poisson_disk(&mut placement, nodes, rng),
+        Topology::Clustered { clusters, spread } => {
+            if clusters == 0 {
+                return Err("clustered placement needs at least one cluster".to_string());
+            }
+
+            let centres: Vec<(f64, f64)> = (0..clusters)
+                .map(|_| (rng.gen_range(0.0..=area.width), rng.gen_range(0.0..=area.height)))
+                .collect();
+
+            while placement.positions.len() < nodes {
+                let (x, y) = centres[rng.gen_range(0..clusters)];
+
+                placement.try_place(
+                    x + gaussian(rng) * spread,
+                    y + gaussian(rng) * spread
+                );
+            }
+        },
+        Topology::PerturbedGrid { jitter } => {
+            let (columns, rows) = grid_shape(area, nodes);
+            let (dx, dy) = (spacing(area.width, columns), spacing(area.height, rows));
+
+            for (x, y) in grid(area, nodes) {
+                let is_placed = (0..100).any(|_| {
+                    let offset_x = rng.gen_range(-1.0..=1.0) * jitter * dx;
+                    let offset_y = rng.gen_range(-1.0..=1.0) * jitter * dy;
+
+                    placement.try_place(x + offset_x, y + offset_y)
+                });
+
+                if !is_placed {
+                    return Err(format!("an area of {}x{} is too small for a {:?} of {} nodes", area.width, area.height, topology, nodes));
+                }
+            }
+        }
+    }
+
+    Ok(placement.positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    const TOPOLOGIES: [Topology; 6] = [
+        Topology::Grid,
+        Topology::HexGrid,
+        Topology::Uniform,
+        Topology::PoissonDisk,
+        Topology::Clustered { clusters: 3, spread: 15.0 },
+        Topology::PerturbedGrid { jitter: 0.25 },
+    ];
+
+    fn area(width: f64, height: f64) -> Area {
+        Area { width, height }
+    }
+
+    #[test]
+    fn generators_place_distinct_nodes_inside_the_area() {
+        for topology in TOPOLOGIES {
+            for (nodes, area) in [(1, area(0.0, 0.0)), (97, area(100.0, 50.0)), (400, area(200.0, 200.0))] {
+                let positions = generate(topology, area, nodes, &mut StdRng::seed_from_u64(7)).unwrap();
+                let distinct: HashSet<(u64, u64)> = positions.iter().map(|position| (position.x.to_bits(), position.y.to_bits())).collect();
+
+                assert_eq!(positions.len(), nodes, "{:?}", topology);
+                assert_eq!(distinct.len(), nodes, "{:?}", topology);
+                assert!(
+                    positions.iter().all(|position| {
+                        (0.0..=area.width).contains(&position.x) && (0.0..=area.height).contains(&position.y) && position.z.is_none()
+                    }),
+                    "{:?}",
+                    topology
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn grids_span_the_area() {
+        let positions = generate(Topology::Grid, area(30.0, 20.0), 15, &mut StdRng::seed_from_u64(0)).unwrap();
+
+        assert_eq!(positions.first(), Some(&Position::new(0.0, 0.0)));
+        assert_eq!(positions.last(), Some(&Position::new(30.0, 20.0)));
+    }
+
+    #[test]
+    fn generators_are_deterministic_for_a_seed() {
+        for topology in TOPOLOGIES {
+            let first = generate(topology, area(100.0, 100.0), 50, &mut StdRng::seed_from_u64(3)).unwrap();
+            let second = generate(topology, area(100.0, 100.0), 50, &mut StdRng::seed_from_u64(3)).unwrap();
+
+            assert_eq!(first, second, "{:?}", topology);
+        }
+    }
+
+    #[test]
+    fn rejects_areas_that_cannot_hold_the_nodes() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert!(generate(Topology::Uniform, area(-1.0, 10.0), 5, &mut rng).is_err());
+        assert!(generate(Topology::Uniform, area(f64::INFINITY, 10.0), 5, &mut rng).is_err());
+        assert!(generate(Topology::Grid, area(0.0, 0.0), 2, &mut rng).is_err());
+        assert!(generate(Topology::Clustered { clusters: 0, spread: 1.0 }, area(10.0, 10.0), 5, &mut rng).is_err());
+    }
+
+    #[test]
+    fn rejects_layouts_the_parser_would_refuse_or_never_finish() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for topology in TOPOLOGIES {
+            assert!(generate(topology, area(10.0, 10.0), 0, &mut rng).is_err(), "{:?}", topology);
+        }
+
+        for value in [f64::NAN, f64::INFINITY, -1.0] {
+            assert!(generate(Topology::Clustered { clusters: 2, spread: value }, area(10.0, 10.0), 5, &mut rng).is_err());
+            assert!(generate(Topology::PerturbedGrid { jitter: value }, area(10.0, 10.0), 5, &mut rng).is_err());
+        }
+
+        assert!(generate(Topology::Clustered { clusters: 2, spread: 0.0 }, area(10.0, 10.0), 1, &mut rng).is_ok());
+    }
+}
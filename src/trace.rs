@@ -0,0 +1,236 @@
+use std::io::{self, Write};
+use serde::{Deserialize, Serialize};
+use crate::messages::message::{Message, MessageKind};
+use crate::node::Node;
+use crate::node_id::NodeId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceOperation {
+    Send,
+    Receive,
+    Drop,
+    Lost,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceNode {
+    pub node: NodeId,
+    pub x: f64,
+    pub y: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub z: Option<f64>,
+    pub neighbours: Vec<NodeId>,
+}
+
+impl TraceNode {
+    pub fn new(node: &Node) -> Self {
+        Self {
+            node: node.id,
+            x: node.position.x,
+            y: node.position.y,
+            z: node.position.z,
+            neighbours: node.neighbour_nodes.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub tick: u32,
+    pub operation: TraceOperation,
+    pub sender: NodeId,
+    pub receiver: NodeId,
+    pub kind: MessageKind,
+    pub hops: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_ids: Option<Vec<u32>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TraceLine {
+    Node(TraceNode),
+    Record(TraceRecord),
+}
+
+impl TraceRecord {
+    pub fn new(tick: u32, operation: TraceOperation, sender: NodeId, receiver: NodeId, message: &Message) -> Self {
+        let mut record = Self {
+            tick,
+            operation,
+            sender,
+            receiver,
+            kind: message.kind(),
+            hops: 0,
+            agent_id: None,
+            request_id: None,
+            sequence: None,
+            event_id: None,
+            event_ids: None,
+        };
+
+        match message {
+            Message::AgentMessage(message) => {
+                record.hops = message.hops;
+                record.agent_id = Some(message.id);
+                record.event_ids = Some({
+                    let mut event_ids: Vec<u32> = message.routes.keys().copied().collect();
+                    event_ids.sort_unstable();
+                    event_ids
+                });
+            },
+            Message::RequestMessage(message) => {
+                record.hops = message.hops;
+                record.request_id = Some(message.id);
+                record.sequence = Some(message.sequence);
+                record.event_id = Some(message.event_id);
+            },
+            Message::ResponseMessage(message) => {
+                record.hops = message.hops;
+                record.request_id = Some(message.id);
+                record.event_id = Some(message.event_id);
+            }
+        }
+
+        record
+    }
+}
+
+pub struct Trace {
+    writer: Box<dyn Write + Send>,
+    error: Option<io::Error>,
+}
+
+impl Trace {
+    pub fn record<T: Serialize>(&mut self, record: &T) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let result = serde_json::to_writer(&mut self.writer, record)
+            .map_err(io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        self.writer.flush()
+    }
+
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer,
+            error: None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use crate::event::Event;
+    use crate::messages::agent_message::AgentMessage;
+    use crate::messages::request_message::RequestMessage;
+    use crate::messages::response_message::ResponseMessage;
+    use crate::position::Position;
+    use crate::route::Route;
+
+    fn line(record: &TraceRecord) -> String {
+        serde_json::to_string(record).unwrap()
+    }
+
+    #[test]
+    fn records_carry_only_the_ids_of_their_message_kind() {
+        let routes: HashMap<u32, Route> = [4, 2].into_iter().map(|id| (id, Route::new(id, NodeId(1), 1))).collect();
+        let agent = Message::AgentMessage(AgentMessage::new(10, 3, vec![NodeId(0)], 7, routes));
+        let request = Message::RequestMessage(RequestMessage::new(11, 2, vec![NodeId(0)], 5, 1, 9, false, 0));
+        let response = Message::ResponseMessage(ResponseMessage::new(12, 1, vec![NodeId(1)], 5, vec![NodeId(0)], 9, Event::new(9, 3, Position::new(0.0, 0.0)), 2));
+
+        assert_eq!(
+            line(&TraceRecord::new(10, TraceOperation::Send, NodeId(0), NodeId(1), &agent)),
+            r#"{"tick":10,"operation":"send","sender":0,"receiver":1,"kind":"agent","hops":3,"agent_id":7,"event_ids":[2,4]}"#
+        );
+        assert_eq!(
+            line(&TraceRecord::new(11, TraceOperation::Lost, NodeId(0), NodeId(1), &request)),
+            r#"{"tick":11,"operation":"lost","sender":0,"receiver":1,"kind":"request","hops":2,"request_id":5,"sequence":1,"event_id":9}"#
+        );
+        assert_eq!(
+            line(&TraceRecord::new(12, TraceOperation::Receive, NodeId(1), NodeId(0), &response)),
+            r#"{"tick":12,"operation":"receive","sender":1,"receiver":0,"kind":"response","hops":1,"request_id":5,"event_id":9}"#
+        );
+    }
+
+    #[test]
+    fn lines_parse_as_nodes_or_records() {
+        let node = r#"{"node":2,"x":1.5,"y":-2.0,"neighbours":[1,3]}"#;
+        let record = r#"{"tick":4,"operation":"drop","sender":2,"receiver":2,"kind":"request","hops":0,"request_id":1,"sequence":0,"event_id":6}"#;
+
+        match serde_json::from_str(node).unwrap() {
+            TraceLine::Node(node) => {
+                assert_eq!(node.node, NodeId(2));
+                assert_eq!(node.z, None);
+                assert_eq!(node.neighbours, vec![NodeId(1), NodeId(3)]);
+            },
+            TraceLine::Record(_) => panic!("a node line parsed as a record"),
+        }
+
+        match serde_json::from_str(record).unwrap() {
+            TraceLine::Record(record) => {
+                assert_eq!(record.operation, TraceOperation::Drop);
+                assert_eq!(record.kind, MessageKind::Request);
+                assert_eq!(record.request_id, Some(1));
+                assert_eq!(record.agent_id, None);
+            },
+            TraceLine::Node(_) => panic!("a record line parsed as a node"),
+        }
+
+        assert!(serde_json::from_str::<TraceLine>(r#"{"tick":4}"#).is_err());
+    }
+
+    #[test]
+    fn nodes_in_3d_keep_their_height() {
+        let node = TraceNode { node: NodeId(0), x: 1.0, y: 2.0, z: Some(3.0), neighbours: Vec::new() };
+
+        assert_eq!(serde_json::to_string(&node).unwrap(), r#"{"node":0,"x":1.0,"y":2.0,"z":3.0,"neighbours":[]}"#);
+    }
+
+    #[test]
+    fn write_errors_surface_when_the_trace_finishes() {
+        struct Failing;
+
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut trace = Trace::new(Box::new(Failing));
+
+        trace.record(&1);
+        trace.record(&2);
+
+        assert_eq!(trace.finish().unwrap_err().to_string(), "disk full");
+        assert!(trace.finish().is_ok());
+    }
+}